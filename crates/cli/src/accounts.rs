@@ -0,0 +1,262 @@
+//! A file of accounts, making `otp` a minimal authenticator.
+//!
+//! The file holds an [`OtpStore`] as JSON. When a passphrase is given it
+//! is [sealed](crate::vault), so that no secret is ever written in
+//! plaintext; a plaintext store is sealed the next time it is saved
+//! with a passphrase.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use otp2::{AccountKey, OtpStore, Totp};
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+use crate::{
+    output::{CodeReport, Output},
+    vault::Sealed,
+    CliResult,
+};
+
+/// The environment variable the passphrase is read from, so that it
+/// never appears in shell history.
+const PASSPHRASE_VAR: &str = "OTP_PASSPHRASE";
+
+#[derive(Debug, Args)]
+pub struct StoreArgs {
+    /// The account store [default: ~/.config/otp/store.json]
+    #[arg(long, value_name = "PATH", env = "OTP_STORE")]
+    store: Option<PathBuf>,
+    /// A file containing the store's passphrase, instead of the
+    /// OTP_PASSPHRASE environment variable
+    #[arg(long, value_name = "PATH")]
+    passphrase_file: Option<PathBuf>,
+}
+
+impl StoreArgs {
+    fn path(&self) -> CliResult<PathBuf> {
+        if let Some(path) = &self.store {
+            return Ok(path.clone());
+        }
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .ok_or("cannot find a configuration directory, so pass --store")?;
+        Ok(config.join("otp").join("store.json"))
+    }
+
+    fn passphrase(&self) -> CliResult<Option<Zeroizing<String>>> {
+        let passphrase = match &self.passphrase_file {
+            Some(path) => Some(
+                fs::read_to_string(path)
+                    .map_err(|e| format!("cannot read {}: {e}", path.display()))?
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string(),
+            ),
+            None => env::var(PASSPHRASE_VAR).ok(),
+        };
+        Ok(passphrase.filter(|p| !p.is_empty()).map(Zeroizing::new))
+    }
+
+    /// Read the store, or an empty store if there is no file yet.
+    fn load(&self) -> CliResult<OtpStore> {
+        let path = self.path()?;
+        let contents = match fs::read(&path) {
+            Ok(contents) => Zeroizing::new(contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(OtpStore::new()),
+            Err(e) => return Err(format!("cannot read {}: {e}", path.display()).into()),
+        };
+        let json: serde_json::Value = serde_json::from_slice(&contents)?;
+        if json.is_object() {
+            let passphrase = self
+                .passphrase()?
+                .ok_or_else(|| format!("the store is encrypted, so set {PASSPHRASE_VAR}"))?;
+            let sealed: Sealed = serde_json::from_value(json)?;
+            Ok(serde_json::from_slice(&sealed.open(&passphrase)?)?)
+        } else {
+            Ok(serde_json::from_value(json)?)
+        }
+    }
+
+    /// Write the store, sealed if there is a passphrase, readable only
+    /// by the user.
+    fn save(&self, store: &OtpStore) -> CliResult<()> {
+        let path = self.path()?;
+        let plaintext = Zeroizing::new(serde_json::to_vec_pretty(store)?);
+        let contents = match self.passphrase()? {
+            Some(passphrase) => serde_json::to_vec_pretty(&Sealed::seal(&plaintext, &passphrase)?)?,
+            None => plaintext.to_vec(),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write a temporary file and rename it over the store, so that
+        // an interrupted write can't lose every account.
+        let tmp = path.with_extension("tmp");
+        write_private(&tmp, &contents)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    fs::write(path, contents)
+}
+
+#[derive(Debug, Args)]
+pub struct AddArgs {
+    /// The `otpauth://totp/` provisioning URI of the account
+    uri: String,
+    #[command(flatten)]
+    store: StoreArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    #[command(flatten)]
+    store: StoreArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct CodeArgs {
+    /// The account, as `issuer:account` or just `account`
+    name: String,
+    #[command(flatten)]
+    store: StoreArgs,
+}
+
+#[derive(Debug, Serialize)]
+struct AddReport<'a> {
+    issuer: Option<&'a str>,
+    account: &'a str,
+    /// Whether an account with the same name was overwritten.
+    replaced: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct AccountCode<'a> {
+    issuer: Option<&'a str>,
+    account: &'a str,
+    code: String,
+}
+
+/// Add an account from its provisioning URI.
+pub fn add(args: AddArgs, output: Output) -> CliResult<()> {
+    let totp = Totp::from_uri(&args.uri)?;
+    let key = AccountKey::new(totp.issuer(), totp.account().unwrap_or_default());
+    let mut store = args.store.load()?;
+    let replaced = store.add(key.clone(), totp).is_some();
+    if replaced && !output.is_json() {
+        eprintln!("otp: replaced the existing account {key}");
+    }
+    args.store.save(&store)?;
+    let report = AddReport {
+        issuer: key.issuer.as_deref(),
+        account: &key.account,
+        replaced,
+    };
+    output.print(&report, format_args!("added {key}"))
+}
+
+/// Print the current code of every account.
+pub fn list(args: ListArgs, output: Output) -> CliResult<()> {
+    let store = args.store.load()?;
+    if !output.is_json() {
+        let mut stdout = io::stdout().lock();
+        for (key, code) in store.all_codes() {
+            writeln!(stdout, "{}  {key}", code?)?;
+        }
+        return Ok(());
+    }
+    let codes = store
+        .all_codes()
+        .map(|(key, code)| {
+            Ok(AccountCode {
+                issuer: key.issuer.as_deref(),
+                account: &key.account,
+                code: code?,
+            })
+        })
+        .collect::<CliResult<Vec<_>>>()?;
+    output.print(&codes, "")
+}
+
+/// Print the current code of one account.
+pub fn code(args: CodeArgs, output: Output) -> CliResult<()> {
+    let store = args.store.load()?;
+    let key = find(&store, &args.name)?;
+    let totp = store.get(key).expect("the account was found");
+    // Formatted by the store, so that the code is the same as `list`'s.
+    let code = store.get_code(key).expect("the account was found")?;
+    let report = CodeReport::with_code(totp, code);
+    output.print(&report, &report.code)
+}
+
+/// The account called `name`, either in full or by its account name
+/// alone if that is unambiguous.
+fn find<'a>(store: &'a OtpStore, name: &str) -> CliResult<&'a AccountKey> {
+    if let Some(key) = store.keys().find(|key| key.to_string() == name) {
+        return Ok(key);
+    }
+    let mut matches = store.keys().filter(|key| key.account == name);
+    match (matches.next(), matches.next()) {
+        (Some(key), None) => Ok(key),
+        (Some(_), Some(_)) => {
+            Err(format!("{name} is ambiguous, so give it as issuer:account").into())
+        }
+        (None, _) => Err(format!("no account called {name}").into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use otp2::{AccountKey, OtpStore, TotpBuilder};
+
+    use crate::accounts::find;
+
+    fn store() -> OtpStore {
+        let mut store = OtpStore::new();
+        for (issuer, account) in [
+            (Some("GitHub"), "alice"),
+            (Some("GitLab"), "alice"),
+            (None, "bob"),
+        ] {
+            let totp = TotpBuilder::new()
+                .secret("12345678901234567890")
+                .build()
+                .unwrap();
+            store.add(AccountKey::new(issuer, account), totp);
+        }
+        store
+    }
+
+    #[test]
+    fn it_finds_accounts_by_name() {
+        let store = store();
+        assert_eq!(
+            find(&store, "GitHub:alice").unwrap(),
+            &AccountKey::new(Some("GitHub"), "alice")
+        );
+        assert_eq!(find(&store, "bob").unwrap(), &AccountKey::new(None, "bob"));
+        assert!(find(&store, "alice").is_err());
+        assert!(find(&store, "carol").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Setting up a new account, by showing the secret and a QR code for an
+//! authenticator app to scan.
+
+use std::io::{self, Write};
+
+use clap::Args;
+use otp2::{format_base32, Totp, DEFAULT_SECRET_LENGTH};
+use serde::Serialize;
+
+use crate::{generator::GeneratorArgs, output::Output, CliResult};
+
+#[derive(Debug, Args)]
+pub struct EnrollArgs {
+    /// The provider the account is for, e.g. `ACME Co`
+    #[arg(long)]
+    issuer: String,
+    /// The user's name for the account, e.g. their email address
+    #[arg(long)]
+    account: String,
+    /// Generate a fresh random secret rather than reading one
+    #[arg(long, conflicts_with_all = ["secret", "secret_file", "secret_fd"])]
+    generate: bool,
+    #[command(flatten)]
+    generator: GeneratorArgs,
+}
+
+/// A new account, for scripts to store or display.
+#[derive(Debug, Serialize)]
+struct EnrollReport {
+    /// The Base32-encoded secret, without spaces.
+    secret: String,
+    uri: String,
+}
+
+/// Print the secret, the provisioning URI and its QR code. As JSON the
+/// QR code is left out.
+pub fn enroll(args: EnrollArgs, output: Output) -> CliResult<()> {
+    let secret = if args.generate {
+        Totp::generate_secret(DEFAULT_SECRET_LENGTH)?
+    } else {
+        args.generator.secret()?
+    };
+    let encoded = format_base32(&secret, false);
+    let grouped = format_base32(&secret, true);
+    let totp = args.generator.totp_with_secret(secret)?;
+    let report = EnrollReport {
+        secret: encoded,
+        uri: totp.to_uri(&args.issuer, &args.account)?,
+    };
+    if output.is_json() {
+        return output.print(&report, "");
+    }
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "Secret: {grouped}")?;
+    writeln!(stdout, "URI:    {}", report.uri)?;
+    writeln!(stdout)?;
+    writeln!(stdout, "{}", totp.qr_terminal(&args.issuer, &args.account)?)?;
+    Ok(())
+}
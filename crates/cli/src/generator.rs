@@ -0,0 +1,72 @@
+//! The options shared by every command which builds a generator.
+
+use clap::{Args, ValueEnum};
+use otp2::{Algorithm, Hotp, HotpBuilder, Totp, TotpBuilder};
+
+use crate::{secret::SecretArgs, CliResult};
+
+#[derive(Debug, Args)]
+pub struct GeneratorArgs {
+    #[command(flatten)]
+    secret: SecretArgs,
+    /// The number of digits in each code
+    #[arg(long, default_value_t = 6)]
+    pub digits: u32,
+    /// The length of each window, in seconds
+    #[arg(long, default_value_t = 30)]
+    period: u64,
+    /// The hash algorithm used to compute the HMAC
+    #[arg(long, value_enum, default_value_t = AlgorithmArg::Sha1)]
+    algorithm: AlgorithmArg,
+}
+
+impl GeneratorArgs {
+    /// Read the secret and build a TOTP generator from the options.
+    pub fn totp(&self) -> CliResult<Totp> {
+        self.totp_with_secret(self.secret()?)
+    }
+
+    /// Read the secret.
+    pub fn secret(&self) -> CliResult<Vec<u8>> {
+        self.secret.read()
+    }
+
+    /// Build a TOTP generator from the options, but with the given
+    /// secret rather than reading one.
+    pub fn totp_with_secret(&self, secret: Vec<u8>) -> CliResult<Totp> {
+        Ok(TotpBuilder::new()
+            .secret(secret)
+            .digits(self.digits)
+            .step(self.period)
+            .algorithm(self.algorithm.into())
+            .build()?)
+    }
+
+    /// Read the secret and build a HOTP generator from the options,
+    /// starting at `counter`. The period is ignored.
+    pub fn hotp(&self, counter: u64) -> CliResult<Hotp> {
+        Ok(HotpBuilder::new()
+            .secret(self.secret.read()?)
+            .counter(counter)
+            .digits(self.digits)
+            .algorithm(self.algorithm.into())
+            .build()?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AlgorithmArg {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl From<AlgorithmArg> for Algorithm {
+    fn from(algorithm: AlgorithmArg) -> Self {
+        match algorithm {
+            AlgorithmArg::Sha1 => Algorithm::Sha1,
+            AlgorithmArg::Sha256 => Algorithm::Sha256,
+            AlgorithmArg::Sha512 => Algorithm::Sha512,
+        }
+    }
+}
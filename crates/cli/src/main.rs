@@ -1,3 +1,115 @@
-fn main() {
-    println!("Hello, world!");
+//! `otp`: one-time passcodes on the command line.
+
+use std::{
+    error::Error,
+    io::{self, ErrorKind},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use otp2::Totp;
+
+use crate::{
+    accounts::{AddArgs, CodeArgs, ListArgs},
+    enroll::EnrollArgs,
+    generator::GeneratorArgs,
+    output::{CodeReport, Output},
+    verify::VerifyArgs,
+};
+
+mod accounts;
+mod enroll;
+mod generator;
+mod output;
+mod secret;
+mod vault;
+mod verify;
+mod watch;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+#[derive(Debug, Parser)]
+#[command(name = "otp", version, about = "Generate one-time passcodes")]
+struct Cli {
+    /// Print results, and errors, as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the current TOTP code and the seconds until it expires
+    Totp(TotpArgs),
+    /// Check a submitted code, exiting non-zero if it is not valid
+    Verify(VerifyArgs),
+    /// Add an account to the store from its otpauth:// URI
+    Add(AddArgs),
+    /// Print the current code of every account in the store
+    List(ListArgs),
+    /// Print the current code of an account in the store
+    Code(CodeArgs),
+    /// Show the secret, URI and QR code for setting up a new account
+    Enroll(EnrollArgs),
+}
+
+#[derive(Debug, clap::Args)]
+struct TotpArgs {
+    #[command(flatten)]
+    generator: GeneratorArgs,
+    /// Keep showing the current code, with a countdown, until Ctrl-C
+    #[arg(long)]
+    watch: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let output = Output::new(cli.json);
+    match run(cli.command, output) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        // The reader went away, e.g. `head` has all the lines it wanted.
+        Err(err) if is_broken_pipe(&*err) => ExitCode::SUCCESS,
+        Err(err) => {
+            output.error(&*err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run a command, returning whether it succeeded.
+fn run(command: Command, output: Output) -> CliResult<bool> {
+    match command {
+        Command::Totp(args) => totp(args, output).map(|()| true),
+        Command::Verify(args) => verify::verify(args, output),
+        Command::Add(args) => accounts::add(args, output).map(|()| true),
+        Command::List(args) => accounts::list(args, output).map(|()| true),
+        Command::Code(args) => accounts::code(args, output).map(|()| true),
+        Command::Enroll(args) => enroll::enroll(args, output).map(|()| true),
+    }
+}
+
+fn totp(args: TotpArgs, output: Output) -> CliResult<()> {
+    let totp = args.generator.totp()?;
+    if args.watch {
+        return watch::watch(&totp, output);
+    }
+    let report = CodeReport::now(&totp)?;
+    output.print(
+        &report,
+        format_args!("{} ({}s remaining)", report.code, report.remaining),
+    )
+}
+
+/// Whether `err` is from writing to a pipe whose reader has gone away.
+fn is_broken_pipe(err: &(dyn Error + 'static)) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == ErrorKind::BrokenPipe)
+}
+
+/// The current code, with leading zeroes.
+fn format_code(totp: &Totp) -> CliResult<String> {
+    let width = totp.digits() as usize;
+    Ok(format!("{:0width$}", totp.current()?))
 }
@@ -0,0 +1,97 @@
+//! Printing results as text for people, or as JSON for scripts.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, Write},
+};
+
+use otp2::Totp;
+use serde::Serialize;
+
+use crate::{format_code, CliResult};
+
+/// How results and errors are printed.
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    json: bool,
+}
+
+impl Output {
+    pub fn new(json: bool) -> Self {
+        Output { json }
+    }
+
+    pub fn is_json(self) -> bool {
+        self.json
+    }
+
+    /// Print `report` as a line of JSON, or `text` otherwise.
+    ///
+    /// Writing fails, rather than panicking as `println!` would, if stdout
+    /// is closed, e.g. by `head`.
+    pub fn print(self, report: &impl Serialize, text: impl Display) -> CliResult<()> {
+        let mut stdout = io::stdout().lock();
+        if self.json {
+            writeln!(stdout, "{}", serde_json::to_string(report)?)?;
+        } else {
+            writeln!(stdout, "{text}")?;
+        }
+        Ok(())
+    }
+
+    /// Report a failed command. As JSON the error goes to stdout with
+    /// everything else, so that a script only has one stream to parse.
+    pub fn error(self, err: &dyn Error) {
+        if self.json {
+            // There is nowhere left to report a failure to write this.
+            let error = serde_json::json!({ "error": err.to_string() });
+            let _ = writeln!(io::stdout().lock(), "{error}");
+        } else {
+            eprintln!("otp: {err}");
+        }
+    }
+}
+
+/// The current code of a TOTP generator.
+#[derive(Debug, Serialize)]
+pub struct CodeReport {
+    pub code: String,
+    /// The seconds until the code expires.
+    pub remaining: u64,
+    pub step: u64,
+}
+
+impl CodeReport {
+    pub fn now(totp: &Totp) -> CliResult<Self> {
+        Ok(CodeReport::with_code(totp, format_code(totp)?))
+    }
+
+    /// Report `code`, already formatted, as the current code of `totp`.
+    pub fn with_code(totp: &Totp, code: String) -> Self {
+        CodeReport {
+            code,
+            remaining: totp.remaining_seconds(),
+            step: totp.step(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use otp2::TotpBuilder;
+
+    use crate::output::CodeReport;
+
+    #[test]
+    fn it_reports_codes_as_json() {
+        let totp = TotpBuilder::new()
+            .secret("12345678901234567890")
+            .build()
+            .unwrap();
+        let report = serde_json::to_value(CodeReport::now(&totp).unwrap()).unwrap();
+        assert_eq!(report["code"].as_str().unwrap().len(), 6);
+        assert!((1..=30).contains(&report["remaining"].as_u64().unwrap()));
+        assert_eq!(report["step"], 30);
+    }
+}
@@ -0,0 +1,125 @@
+//! Reading the shared secret from wherever the user keeps it.
+
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+
+use crate::CliResult;
+
+/// The environment variable the secret can be read from.
+const SECRET_VAR: &str = "OTP_SECRET";
+
+/// Where to read the Base32-encoded secret from.
+///
+/// In order of precedence, the secret is read from:
+///
+/// 1. a file or descriptor, with `--secret-file` or `--secret-fd`;
+/// 2. the `OTP_SECRET` environment variable;
+/// 3. `--secret`, which warns, since other users can see the arguments
+///    of a running process and the shell may save them in its history;
+/// 4. stdin.
+#[derive(Debug, Args)]
+pub struct SecretArgs {
+    /// The Base32-encoded secret, ignored if OTP_SECRET is set. Prefer
+    /// OTP_SECRET or --secret-file, which keep the secret out of `ps` and
+    /// shell history
+    #[arg(long, conflicts_with_all = ["secret_file", "secret_fd"])]
+    secret: Option<String>,
+    /// A file containing the Base32-encoded secret, or `-` for stdin
+    #[arg(long, value_name = "PATH", conflicts_with = "secret_fd")]
+    secret_file: Option<PathBuf>,
+    /// An open file descriptor to read the Base32-encoded secret from
+    #[arg(long, value_name = "FD")]
+    secret_fd: Option<u32>,
+}
+
+impl SecretArgs {
+    /// Read and decode the secret.
+    pub fn read(&self) -> CliResult<Vec<u8>> {
+        if let Some(path) = self.path() {
+            return decode(&read_file(&path)?);
+        }
+        if let Some(secret) = env::var_os(SECRET_VAR) {
+            if self.secret.is_some() {
+                eprintln!("otp: warning: ignoring --secret in favour of {SECRET_VAR}");
+            }
+            let secret = secret
+                .into_string()
+                .map_err(|_| format!("{SECRET_VAR} is not valid UTF-8"))?;
+            return decode(&secret);
+        }
+        if let Some(secret) = &self.secret {
+            eprintln!(
+                "otp: warning: --secret can be seen by other users and saved in shell history, \
+                 so prefer {SECRET_VAR} or --secret-file"
+            );
+            return decode(secret);
+        }
+        decode(&read_file(Path::new("-"))?)
+    }
+
+    /// The file to read the secret from, if one was given.
+    fn path(&self) -> Option<PathBuf> {
+        if let Some(fd) = self.secret_fd {
+            return Some(PathBuf::from(format!("/dev/fd/{fd}")));
+        }
+        self.secret_file.clone()
+    }
+}
+
+/// Read a file, or stdin for `-`.
+fn read_file(path: &Path) -> CliResult<String> {
+    if path.as_os_str() != "-" {
+        return Ok(
+            fs::read_to_string(path).map_err(|e| format!("cannot read {}: {e}", path.display()))?
+        );
+    }
+    let mut secret = String::new();
+    io::stdin()
+        .read_to_string(&mut secret)
+        .map_err(|e| format!("cannot read the secret from stdin: {e}"))?;
+    Ok(secret)
+}
+
+/// Decode a secret, ignoring the surrounding whitespace a file or pipe
+/// usually adds.
+fn decode(secret: &str) -> CliResult<Vec<u8>> {
+    let secret = secret.trim();
+    if secret.is_empty() {
+        return Err("no secret was given".into());
+    }
+    Ok(otp2::parse_base32(secret)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::secret::{decode, SecretArgs};
+
+    #[test]
+    fn it_decodes_secrets_with_a_trailing_newline() {
+        let secret = decode("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\n").unwrap();
+        assert_eq!(secret, b"12345678901234567890");
+    }
+
+    #[test]
+    fn it_rejects_missing_and_invalid_secrets() {
+        assert!(decode(" \n").is_err());
+        assert!(decode("GEZDGNBVGY3TQOJQ0").is_err());
+    }
+
+    #[test]
+    fn it_reads_descriptors_through_dev_fd() {
+        let args = SecretArgs {
+            secret: None,
+            secret_file: None,
+            secret_fd: Some(3),
+        };
+        assert_eq!(args.path(), Some(PathBuf::from("/dev/fd/3")));
+    }
+}
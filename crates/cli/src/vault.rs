@@ -0,0 +1,174 @@
+//! Encrypting the account store at rest with a passphrase.
+//!
+//! The key is derived from the passphrase with Argon2id and a random
+//! salt, and the store is sealed with ChaCha20-Poly1305, so that a
+//! wrong passphrase or a tampered file is detected rather than
+//! decrypting to garbage. The Argon2 parameters are kept with the
+//! store, so that it can still be opened if the `argon2` crate's
+//! defaults change.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, KeyInit, Nonce};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::CliResult;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+
+/// A sealed store, as written to disk. The binary fields are
+/// hex-encoded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sealed {
+    /// Stores written before the parameters were kept used the defaults.
+    #[serde(default)]
+    kdf: Kdf,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl Sealed {
+    /// Encrypt `plaintext` with a key derived from `passphrase`.
+    pub fn seal(plaintext: &[u8], passphrase: &str) -> CliResult<Self> {
+        let mut salt = [0; SALT_LENGTH];
+        let mut nonce = [0; NONCE_LENGTH];
+        getrandom::fill(&mut salt)?;
+        getrandom::fill(&mut nonce)?;
+        let kdf = Kdf::default();
+        let cipher = kdf.cipher(passphrase, &salt)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| "failed to encrypt the store")?;
+        Ok(Sealed {
+            kdf,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// Decrypt the store, failing if the passphrase is wrong or the file
+    /// has been altered.
+    pub fn open(&self, passphrase: &str) -> CliResult<Zeroizing<Vec<u8>>> {
+        let salt = hex::decode(&self.salt)?;
+        let nonce = hex::decode(&self.nonce)?;
+        if nonce.len() != NONCE_LENGTH {
+            return Err("the store's nonce is malformed".into());
+        }
+        let cipher = self.kdf.cipher(passphrase, &salt)?;
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                hex::decode(&self.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| "wrong passphrase, or the store has been altered")?;
+        Ok(Zeroizing::new(plaintext))
+    }
+}
+
+/// How the key is derived from the passphrase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Kdf {
+    algorithm: String,
+    version: u32,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+/// Argon2id with the parameters `argon2` 0.5 uses by default, spelled
+/// out so that an upgrade can't change them.
+impl Default for Kdf {
+    fn default() -> Self {
+        Kdf {
+            algorithm: Algorithm::Argon2id.as_str().to_string(),
+            version: Version::V0x13.into(),
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+impl Kdf {
+    /// The cipher keyed with the key derived from `passphrase`.
+    fn cipher(&self, passphrase: &str, salt: &[u8]) -> CliResult<ChaCha20Poly1305> {
+        let unsupported = |e| format!("the store's key derivation is not supported: {e}");
+        let algorithm: Algorithm = self.algorithm.parse().map_err(unsupported)?;
+        let version = Version::try_from(self.version).map_err(unsupported)?;
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LENGTH))
+            .map_err(unsupported)?;
+        let mut key = Zeroizing::new([0; KEY_LENGTH]);
+        Argon2::new(algorithm, version, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+            .map_err(|e| format!("failed to derive the store key: {e}"))?;
+        Ok(ChaCha20Poly1305::new(key.as_ref().into()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use argon2::Params;
+
+    use crate::vault::{Kdf, Sealed};
+
+    #[test]
+    fn it_round_trips_with_the_passphrase() {
+        let sealed = Sealed::seal(b"[]", "correct horse").unwrap();
+        assert_eq!(sealed.open("correct horse").unwrap().as_slice(), b"[]");
+        assert!(sealed.open("battery staple").is_err());
+    }
+
+    #[test]
+    fn it_hides_the_plaintext() {
+        let plaintext = b"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let sealed = Sealed::seal(plaintext, "correct horse").unwrap();
+        let json = serde_json::to_string(&sealed).unwrap();
+        assert!(!json.contains("GEZDGNBV"));
+        assert!(!json.contains(&hex::encode(plaintext)));
+    }
+
+    #[test]
+    fn it_detects_tampering() {
+        let mut sealed = Sealed::seal(b"[]", "correct horse").unwrap();
+        let mut ciphertext = hex::decode(&sealed.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        sealed.ciphertext = hex::encode(ciphertext);
+        assert!(sealed.open("correct horse").is_err());
+    }
+
+    #[test]
+    fn it_records_the_key_derivation() {
+        let sealed = Sealed::seal(b"[]", "correct horse").unwrap();
+        let json = serde_json::to_value(&sealed).unwrap();
+        assert_eq!(
+            json["kdf"],
+            serde_json::json!({
+                "algorithm": "argon2id",
+                "version": 19,
+                "m_cost": 19456,
+                "t_cost": 2,
+                "p_cost": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn it_opens_stores_with_their_own_parameters() {
+        let mut sealed = Sealed::seal(b"[]", "correct horse").unwrap();
+        // Stores written before the parameters were kept have none.
+        let mut json = serde_json::to_value(&sealed).unwrap();
+        json.as_object_mut().unwrap().remove("kdf");
+        let old: Sealed = serde_json::from_value(json).unwrap();
+        assert_eq!(old.open("correct horse").unwrap().as_slice(), b"[]");
+        // Other parameters derive another key.
+        sealed.kdf = Kdf {
+            t_cost: Params::DEFAULT_T_COST + 1,
+            ..Kdf::default()
+        };
+        assert!(sealed.open("correct horse").is_err());
+    }
+}
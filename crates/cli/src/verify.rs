@@ -0,0 +1,98 @@
+//! Checking a submitted code, so that scripts can gate actions on it.
+
+use clap::Args;
+use serde::Serialize;
+
+use crate::{generator::GeneratorArgs, is_broken_pipe, output::Output, CliResult};
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    generator: GeneratorArgs,
+    /// The code to check, with any leading zeroes
+    #[arg(long)]
+    code: String,
+    /// The number of windows either side of the current one to accept
+    #[arg(long, default_value_t = 1, conflicts_with = "hotp")]
+    skew: u64,
+    /// Check a HOTP code rather than a TOTP code
+    #[arg(long)]
+    hotp: bool,
+    /// The HOTP counter the code is expected to be for
+    #[arg(long, default_value_t = 0, requires = "hotp")]
+    counter: u64,
+    /// The number of HOTP counters after `--counter` to accept
+    #[arg(long, default_value_t = 1, requires = "hotp")]
+    look_ahead: u64,
+}
+
+/// The outcome of a check: where the code matched, if it did.
+#[derive(Debug, Default, Serialize)]
+struct VerifyReport {
+    valid: bool,
+    /// The TOTP window the code matched, relative to the current one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<i64>,
+    /// The HOTP counter the code matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counter: Option<u64>,
+}
+
+/// Check the code, printing where it matched, and return whether it is
+/// valid.
+pub fn verify(args: VerifyArgs, output: Output) -> CliResult<bool> {
+    let report = if args.hotp {
+        let mut hotp = args.generator.hotp(args.counter)?;
+        let counter = hotp.verify_str(&args.code, args.look_ahead);
+        VerifyReport {
+            valid: counter.is_some(),
+            counter,
+            ..VerifyReport::default()
+        }
+    } else {
+        let totp = args.generator.totp()?;
+        let offset = totp.verify_str_with_skew_detail(&args.code, args.skew);
+        VerifyReport {
+            valid: offset.is_some(),
+            offset,
+            ..VerifyReport::default()
+        }
+    };
+    let printed = match (report.offset, report.counter) {
+        (Some(offset), _) => output.print(&report, format_args!("valid (offset {offset:+})")),
+        (_, Some(counter)) => output.print(&report, format_args!("valid (counter {counter})")),
+        _ if output.is_json() => output.print(&report, ""),
+        _ => {
+            eprintln!("otp: invalid code");
+            Ok(())
+        }
+    };
+    // Even if nobody is reading the output, the exit status must still
+    // say whether the code was valid.
+    match printed {
+        Err(err) if !is_broken_pipe(&*err) => Err(err),
+        _ => Ok(report.valid),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::verify::VerifyReport;
+
+    #[test]
+    fn it_reports_only_where_the_code_matched() {
+        let report = VerifyReport {
+            valid: true,
+            offset: Some(-1),
+            ..VerifyReport::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"valid":true,"offset":-1}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&VerifyReport::default()).unwrap(),
+            r#"{"valid":false}"#
+        );
+    }
+}
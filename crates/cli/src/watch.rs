@@ -0,0 +1,89 @@
+//! Showing the code live in the terminal, with a countdown to when it
+//! expires.
+
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use otp2::Totp;
+
+use crate::{
+    format_code,
+    output::{CodeReport, Output},
+    CliResult,
+};
+
+/// The width of the progress bar, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// How often the countdown is redrawn.
+const TICK: Duration = Duration::from_millis(200);
+
+/// Redraw the current code and countdown in place until interrupted.
+///
+/// As JSON, a line is printed for each new code instead.
+pub fn watch(totp: &Totp, output: Output) -> CliResult<()> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler = Arc::clone(&interrupted);
+    ctrlc::set_handler(move || handler.store(true, Ordering::SeqCst))?;
+
+    if output.is_json() {
+        let mut last = None;
+        while !interrupted.load(Ordering::SeqCst) {
+            let report = CodeReport::now(totp)?;
+            if last.as_ref() != Some(&report.code) {
+                output.print(&report, "")?;
+                last = Some(report.code);
+            }
+            thread::sleep(TICK);
+        }
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout().lock();
+    while !interrupted.load(Ordering::SeqCst) {
+        let line = status_line(
+            &format_code(totp)?,
+            totp.progress(),
+            totp.remaining_seconds(),
+        );
+        // Return to the start of the line and clear it, so the line is
+        // redrawn rather than scrolling.
+        write!(stdout, "\r\x1b[2K{line}")?;
+        stdout.flush()?;
+        thread::sleep(TICK);
+    }
+    writeln!(stdout)?;
+    Ok(())
+}
+
+/// The code, a bar of the time left in its window, and the seconds left.
+fn status_line(code: &str, progress: f64, remaining: u64) -> String {
+    let left = ((1.0 - progress) * BAR_WIDTH as f64).ceil() as usize;
+    let left = left.min(BAR_WIDTH);
+    format!(
+        "{code} [{}{}] {remaining:>2}s",
+        "#".repeat(left),
+        " ".repeat(BAR_WIDTH - left)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use crate::watch::status_line;
+
+    #[test_case(0.0, 30 => "287082 [##############################] 30s" ; "full window")]
+    #[test_case(0.5, 15 => "287082 [###############               ] 15s" ; "halfway")]
+    #[test_case(0.99, 1 => "287082 [#                             ]  1s" ; "about to expire")]
+    fn it_draws_the_countdown(progress: f64, remaining: u64) -> String {
+        status_line("287082", progress, remaining)
+    }
+}
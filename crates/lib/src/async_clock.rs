@@ -0,0 +1,85 @@
+//! TOTP with an asynchronous time source, e.g. a clock service which
+//! must be queried over the network.
+//!
+//! The generator's own "now" function is not used; instead each call
+//! awaits the given `now` function for the time.
+
+use std::future::Future;
+
+use unix_time::Instant;
+
+use crate::{OtpResult, Totp};
+
+impl<F: Fn() -> Instant> Totp<F> {
+    /// Await the current time from `now`, then compute the passcode for
+    /// that time as by [`Self::get_at`].
+    pub async fn get_async<C, Fut>(&self, now: C) -> OtpResult<u32>
+    where
+        C: FnOnce() -> Fut,
+        Fut: Future<Output = Instant>,
+    {
+        let at = now().await;
+        self.get_at(at)
+    }
+
+    /// Await the current time from `now`, then check `code` as by
+    /// [`Self::verify_with_skew`] at that time.
+    pub async fn verify_async<C, Fut>(&self, code: u32, skew: u64, now: C) -> bool
+    where
+        C: FnOnce() -> Fut,
+        Fut: Future<Output = Instant>,
+    {
+        let at = now().await;
+        let current = self.generator.steps_at(at);
+        self.verify_around_step(code, skew, skew, current).is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use unix_time::Instant;
+
+    use crate::Totp;
+
+    /// Poll `future` to completion. The futures here never wait, so one
+    /// poll suffices.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut context = Context::from_waker(Waker::noop());
+        match pin!(future).poll(&mut context) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future was not ready"),
+        }
+    }
+
+    fn totp() -> Totp<impl Fn() -> Instant> {
+        // The generator's own clock must not be consulted
+        let now = || unreachable!();
+        Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now).unwrap()
+    }
+
+    #[test]
+    fn it_gets_the_code_from_an_async_clock() {
+        let code = block_on(totp().get_async(|| async { Instant::at(59, 0) }));
+        assert_eq!(code.unwrap(), 94287082);
+    }
+
+    #[test]
+    fn it_verifies_against_an_async_clock() {
+        let otp = totp();
+        assert!(block_on(
+            otp.verify_async(94287082, 0, || async { Instant::at(59, 0) })
+        ));
+        assert!(block_on(
+            otp.verify_async(94287082, 1, || async { Instant::at(65, 0) })
+        ));
+        assert!(!block_on(
+            otp.verify_async(94287082, 0, || async { Instant::at(65, 0) })
+        ));
+    }
+}
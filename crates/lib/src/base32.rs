@@ -0,0 +1,187 @@
+//! RFC 4648 Base32, which is how provisioning URIs and authenticator
+//! apps store the shared secret.
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::{HotpError, OtpResult};
+
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Encode `bytes` as unpadded, upper-case Base32, the form expected in
+/// provisioning URIs.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[(buffer >> bits) as usize & 0b11111] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[(buffer << (5 - bits)) as usize & 0b11111] as char);
+    }
+    encoded
+}
+
+/// Encode a `secret` as unpadded, upper-case Base32 for users to type
+/// into an authenticator app which can't scan a QR code.
+///
+/// When `grouped`, the symbols are split into groups of four by spaces,
+/// which are ignored when the secret is read back, e.g. by
+/// [`Totp::from_base32`](crate::Totp::from_base32).
+///
+/// ```
+/// let secret = b"Hello!\xde\xad\xbe\xef";
+/// assert_eq!(otp2::format_base32(secret, true), "JBSW Y3DP EHPK 3PXP");
+/// assert_eq!(otp2::format_base32(secret, false), "JBSWY3DPEHPK3PXP");
+/// ```
+pub fn format_base32(secret: &[u8], grouped: bool) -> String {
+    let encoded = encode(secret);
+    if !grouped {
+        return encoded;
+    }
+    let groups: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(4)
+        .map(|group| core::str::from_utf8(group).expect("Base32 is ASCII"))
+        .collect();
+    groups.join(" ")
+}
+
+/// Decode a Base32 secret, as typed by users or read from a
+/// provisioning URI, into bytes. The reverse of [`format_base32`].
+///
+/// Decoding is case-insensitive, trailing `=` padding is optional, and
+/// whitespace is ignored.
+///
+/// ```
+/// assert_eq!(otp2::parse_base32("jbsw y3dp ehpk 3pxp")?, b"Hello!\xde\xad\xbe\xef");
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+pub fn parse_base32(secret: &str) -> OtpResult<Vec<u8>> {
+    decode(secret)
+}
+
+/// Decode a Base32 `input` into bytes.
+///
+/// Decoding is case-insensitive, trailing `=` padding is optional, and
+/// whitespace (e.g. secrets copied in groups of four) is ignored.
+pub(crate) fn decode(input: &str) -> OtpResult<Vec<u8>> {
+    let input: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let input = input.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in input.chars() {
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u32 - 'A' as u32,
+            c @ '2'..='7' => c as u32 - '2' as u32 + 26,
+            _ => return Err(HotpError::InvalidBase32Character(c)),
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // A whole group of five bits left over means the input was truncated
+    // part way through a byte.
+    if bits >= 5 {
+        return Err(HotpError::InvalidBase32 {
+            err: format!("invalid length {}", input.len()),
+        });
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use crate::{
+        base32::{decode, encode, format_base32},
+        HotpError,
+    };
+
+    // These test cases are copied from RFC 4648
+    // https://datatracker.ietf.org/doc/html/rfc4648#section-10
+    #[test_case("", "")]
+    #[test_case("MY======", "f")]
+    #[test_case("MZXQ====", "fo")]
+    #[test_case("MZXW6===", "foo")]
+    #[test_case("MZXW6YQ=", "foob")]
+    #[test_case("MZXW6YTB", "fooba")]
+    #[test_case("MZXW6YTBOI======", "foobar")]
+    fn it_decodes_rfc_vectors(input: &str, expected: &str) {
+        assert_eq!(decode(input).unwrap(), expected.as_bytes());
+    }
+
+    #[test_case("", "")]
+    #[test_case("f", "MY")]
+    #[test_case("fo", "MZXQ")]
+    #[test_case("foo", "MZXW6")]
+    #[test_case("foob", "MZXW6YQ")]
+    #[test_case("fooba", "MZXW6YTB")]
+    #[test_case("foobar", "MZXW6YTBOI")]
+    fn it_encodes_rfc_vectors_without_padding(input: &str, expected: &str) {
+        assert_eq!(encode(input.as_bytes()), expected);
+        assert_eq!(decode(expected).unwrap(), input.as_bytes());
+    }
+
+    #[test]
+    fn it_decodes_the_rfc_4226_secret() {
+        let actual = decode("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+        assert_eq!(actual, b"12345678901234567890");
+    }
+
+    #[test]
+    fn it_decodes_case_insensitively_without_padding() {
+        assert_eq!(decode("mzxw6yq").unwrap(), b"foob");
+    }
+
+    #[test]
+    fn it_ignores_whitespace() {
+        let canonical = decode("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+        let spaced = decode("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap();
+        assert_eq!(spaced, canonical);
+        assert_eq!(decode(" mzxw 6yq\n").unwrap(), decode("MZXW6YQ=").unwrap());
+    }
+
+    #[test_case(b"", "")]
+    #[test_case(b"f", "MY")]
+    #[test_case(b"foobar", "MZXW 6YTB OI")]
+    #[test_case(b"12345678901234567890", "GEZD GNBV GY3T QOJQ GEZD GNBV GY3T QOJQ")]
+    fn it_formats_secrets_in_groups(secret: &[u8], expected: &str) {
+        assert_eq!(format_base32(secret, true), expected);
+        assert_eq!(decode(expected).unwrap(), secret);
+    }
+
+    #[test_case(b"foobar")]
+    #[test_case(b"12345678901234567890")]
+    fn it_formats_secrets_that_decode_to_the_original(secret: &[u8]) {
+        let formatted = format_base32(secret, false);
+        assert!(!formatted.contains(' '));
+        assert_eq!(decode(&formatted).unwrap(), secret);
+    }
+
+    #[test_case("MZXW6YQ1", '1')]
+    #[test_case("MZ=XW6YQ", '=')]
+    #[test_case("MZXW-6YQ", '-')]
+    fn it_rejects_characters_outside_the_alphabet(input: &str, expected: char) {
+        let err = decode(input).unwrap_err();
+        assert!(matches!(err, HotpError::InvalidBase32Character(c) if c == expected));
+    }
+
+    #[test]
+    fn it_rejects_truncated_input() {
+        let err = decode("MZXW6YTBO").unwrap_err();
+        assert!(matches!(err, HotpError::InvalidBase32 { .. }));
+    }
+}
@@ -0,0 +1,88 @@
+//! Interop with [`chrono`], for applications which already keep time as
+//! a `DateTime<Utc>`.
+
+use chrono::{DateTime, Utc};
+use unix_time::Instant;
+
+use crate::{OtpResult, Totp};
+
+impl Totp {
+    /// Get a TOTP generator counting steps from a [`DateTime<Utc>`], with
+    /// [`Utc::now`] providing the "now" value.
+    ///
+    /// The time is converted by its Unix timestamp. Times before the
+    /// epoch are treated as the epoch itself.
+    ///
+    /// See [`Self::new`].
+    pub fn new_from_datetime(
+        key: impl Into<Vec<u8>>,
+        t0: DateTime<Utc>,
+        step: u64,
+        length: u32,
+    ) -> OtpResult<Self> {
+        Totp::new_with_now(key, instant_from_datetime(t0), step, length, chrono_now)
+    }
+
+    /// Get a TOTP generator counting steps from a [`DateTime<Utc>`], with
+    /// a custom function providing the "now" value.
+    ///
+    /// See [`Self::new_from_datetime`].
+    pub fn new_from_datetime_with_now<N: Fn() -> DateTime<Utc>>(
+        key: impl Into<Vec<u8>>,
+        t0: DateTime<Utc>,
+        step: u64,
+        length: u32,
+        now: N,
+    ) -> OtpResult<Totp<impl Fn() -> Instant>> {
+        let now = move || instant_from_datetime(now());
+        Totp::new_with_now(key, instant_from_datetime(t0), step, length, now)
+    }
+}
+
+fn chrono_now() -> Instant {
+    instant_from_datetime(Utc::now())
+}
+
+fn instant_from_datetime(time: DateTime<Utc>) -> Instant {
+    match u64::try_from(time.timestamp()) {
+        Ok(secs) => Instant::at(secs, time.timestamp_subsec_nanos()),
+        Err(_) => Instant::at(0, 0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, TimeZone, Utc};
+    use unix_time::Instant;
+
+    use crate::{chrono_clock::instant_from_datetime, Totp};
+
+    #[test]
+    fn it_computes_a_code_at_a_fixed_datetime() {
+        // From RFC 6238 appendix B
+        let now = || Utc.with_ymd_and_hms(2005, 3, 18, 1, 58, 29).unwrap();
+        let mut otp = Totp::new_from_datetime_with_now(
+            "12345678901234567890",
+            DateTime::UNIX_EPOCH,
+            30,
+            8,
+            now,
+        )
+        .unwrap();
+        assert_eq!(otp.get().unwrap(), 7081804);
+    }
+
+    #[test]
+    fn it_converts_datetimes_by_their_timestamp() {
+        let t0 = Utc.timestamp_opt(1_000_000_000, 500).unwrap();
+        assert_eq!(instant_from_datetime(t0), Instant::at(1_000_000_000, 500));
+        let otp = Totp::new_from_datetime("12345678901234567890", t0, 30, 8).unwrap();
+        assert_eq!(otp.generator.t0, Instant::at(1_000_000_000, 500));
+    }
+
+    #[test]
+    fn it_clamps_datetimes_before_the_epoch() {
+        let t0 = Utc.with_ymd_and_hms(1969, 12, 31, 23, 59, 0).unwrap();
+        assert_eq!(instant_from_datetime(t0), Instant::at(0, 0));
+    }
+}
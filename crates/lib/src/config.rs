@@ -0,0 +1,235 @@
+//! Serializable settings for generators, so that accounts can be kept
+//! in e.g. a JSON configuration file.
+//!
+//! The secret is stored Base32-encoded, as in provisioning URIs. Only
+//! decimal passcodes are described, and a TOTP generator's custom
+//! "now" function is not kept.
+
+use alloc::string::String;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use unix_time::Instant;
+use zeroize::Zeroizing;
+
+use crate::{
+    base32, Algorithm, Encoding, Hotp, HotpError, Otp, OtpResult, ToBytes, MAX_CHECKSUM_DIGITS,
+};
+#[cfg(feature = "std")]
+use crate::{totp::SYSTEM_NOW, Totp};
+
+/// The settings of a [`Totp`] generator.
+#[cfg(feature = "std")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpConfig {
+    /// The Base32-encoded shared secret.
+    pub secret: Zeroizing<String>,
+    pub digits: u32,
+    /// The length of each window, in seconds.
+    pub step: u64,
+    /// The Unix time, in seconds, from which steps are counted.
+    #[serde(default)]
+    pub t0: u64,
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+}
+
+/// The settings of a [`Hotp`] generator.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HotpConfig {
+    /// The Base32-encoded shared secret.
+    pub secret: Zeroizing<String>,
+    pub digits: u32,
+    /// The counter the next passcode will be computed from.
+    pub counter: u64,
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    /// Whether an RFC 4226 checksum digit follows each passcode.
+    #[serde(default)]
+    pub checksum: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+}
+
+#[cfg(feature = "std")]
+impl<F: Fn() -> Instant> Totp<F> {
+    /// Describe the generator's settings, for serialization.
+    ///
+    /// Fails if the HMAC is computed externally, since the secret is
+    /// then unknown, or uses a digest from [`HmacOtp`](crate::HmacOtp), or
+    /// if the passcodes aren't decimal, e.g. for [`Totp::new_steam`].
+    pub fn to_config(&self) -> OtpResult<TotpConfig> {
+        check_decimal(self)?;
+        Ok(TotpConfig {
+            secret: Zeroizing::new(base32::encode(self.key.secret_bytes()?)),
+            digits: self.digits,
+            step: self.generator.step,
+            t0: self.generator.t0.secs(),
+            algorithm: self.key.standard_algorithm()?,
+            issuer: self.issuer.clone(),
+            account: self.account.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl TotpConfig {
+    /// Build the generator described by these settings.
+    pub fn build(&self) -> OtpResult<Totp> {
+        let mut totp = Totp::new_with_algorithm(
+            base32::decode(&self.secret)?,
+            Instant::at(self.t0, 0),
+            self.step,
+            self.digits,
+            self.algorithm,
+            SYSTEM_NOW,
+        )?;
+        totp.issuer = self.issuer.clone();
+        totp.account = self.account.clone();
+        Ok(totp)
+    }
+}
+
+impl Hotp {
+    /// Describe the generator's settings, including its current
+    /// counter, for serialization.
+    ///
+    /// Fails if the HMAC is computed externally, since the secret is
+    /// then unknown, or uses a digest from [`HmacOtp`](crate::HmacOtp), or
+    /// if the passcodes aren't decimal.
+    pub fn to_config(&self) -> OtpResult<HotpConfig> {
+        check_decimal(self)?;
+        Ok(HotpConfig {
+            secret: Zeroizing::new(base32::encode(self.key.secret_bytes()?)),
+            digits: self.digits,
+            counter: self.count(),
+            algorithm: self.key.standard_algorithm()?,
+            checksum: self.checksum,
+            issuer: self.issuer.clone(),
+            account: self.account.clone(),
+        })
+    }
+}
+
+impl HotpConfig {
+    /// Build the generator described by these settings.
+    ///
+    /// With a checksum digit, there may be at most
+    /// [`MAX_CHECKSUM_DIGITS`](crate::MAX_CHECKSUM_DIGITS) others.
+    pub fn build(&self) -> OtpResult<Hotp> {
+        if self.checksum && self.digits > MAX_CHECKSUM_DIGITS {
+            return Err(HotpError::InvalidChecksumDigits {
+                digits: self.digits,
+            });
+        }
+        let mut hotp = Hotp::new_with_algorithm(
+            base32::decode(&self.secret)?,
+            self.counter,
+            self.digits,
+            self.algorithm,
+        )?;
+        hotp.checksum = self.checksum;
+        hotp.issuer = self.issuer.clone();
+        hotp.account = self.account.clone();
+        Ok(hotp)
+    }
+}
+
+/// Only decimal passcodes are described, so that a generator with
+/// another alphabet doesn't come back as a decimal one.
+fn check_decimal<G: ToBytes>(otp: &Otp<G>) -> OtpResult<()> {
+    if otp.encoding != Encoding::decimal() {
+        return Err(HotpError::UnsupportedConfig {
+            reason: "passcodes must be decimal",
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "std")]
+    use unix_time::Instant;
+
+    #[cfg(feature = "std")]
+    use crate::{Algorithm, HotpError, Totp, TotpConfig};
+    use crate::{Hotp, HotpBuilder, HotpConfig};
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn it_round_trips_a_totp_config() {
+        let now = Box::new(|| Instant::at(59, 0));
+        let totp = Totp::new_with_algorithm(
+            "12345678901234567890123456789012",
+            Instant::at(0, 0),
+            30,
+            8,
+            Algorithm::Sha256,
+            now,
+        )
+        .unwrap()
+        .with_issuer("ACME Co");
+        let json = serde_json::to_string(&totp.to_config().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"secret":"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA","digits":8,"step":30,"t0":0,"algorithm":"SHA256","issuer":"ACME Co"}"#
+        );
+        let config: TotpConfig = serde_json::from_str(&json).unwrap();
+        let restored = config.build().unwrap();
+        let at = Instant::at(59, 0);
+        assert_eq!(restored.get_at(at).unwrap(), totp.get_at(at).unwrap());
+        assert_eq!(restored.issuer(), Some("ACME Co"));
+        assert_eq!(restored.account(), None);
+    }
+
+    #[test]
+    fn it_round_trips_a_hotp_config() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        hotp.get().unwrap();
+        let json = serde_json::to_string(&hotp.to_config().unwrap()).unwrap();
+        let config: HotpConfig = serde_json::from_str(&json).unwrap();
+        let mut restored = config.build().unwrap();
+        assert_eq!(restored.get().unwrap(), 287082);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn it_refuses_to_describe_steam_generators() {
+        let totp = Totp::new_steam("12345678901234567890").unwrap();
+        assert!(matches!(
+            totp.to_config(),
+            Err(HotpError::UnsupportedConfig { .. })
+        ));
+    }
+
+    #[test]
+    fn it_round_trips_a_checksum_digit() {
+        let hotp = HotpBuilder::new()
+            .secret("12345678901234567890")
+            .checksum(true)
+            .build()
+            .unwrap();
+        let json = serde_json::to_string(&hotp.to_config().unwrap()).unwrap();
+        let config: HotpConfig = serde_json::from_str(&json).unwrap();
+        let restored = config.build().unwrap();
+        assert_eq!(hotp.peek().unwrap(), 7552243);
+        assert_eq!(restored.peek().unwrap(), 7552243);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn it_applies_defaults_when_deserializing() {
+        let json = r#"{"secret":"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ","digits":6,"step":30}"#;
+        let config: TotpConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.algorithm, Algorithm::Sha1);
+        assert_eq!(config.t0, 0);
+        let totp = config.build().unwrap();
+        assert_eq!(totp.get_at(Instant::at(59, 0)).unwrap(), 287082);
+    }
+}
@@ -0,0 +1,324 @@
+//! A C interface, for embedding the crate as a shared library in other
+//! languages.
+//!
+//! A generator is created with [`otp_totp_new`], which hands back an
+//! opaque handle that must eventually be passed to [`otp_totp_free`].
+//! Every function returns an [`OtpStatus`] rather than panicking, and
+//! checks its pointers for null before using them.
+//!
+//! Build a shared library with
+//! `cargo rustc -p otp2 --features ffi --crate-type cdylib`.
+//!
+//! ```c
+//! OtpTotp *totp;
+//! char code[11];
+//! if (otp_totp_new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", 6, 30, OTP_SHA1, &totp) == 0) {
+//!     otp_totp_generate(totp, code, sizeof code);
+//!     otp_totp_verify(totp, code, 1); /* 0, i.e. accepted */
+//!     otp_totp_free(totp);
+//! }
+//! ```
+
+use std::ffi::{c_char, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use unix_time::Instant;
+
+use crate::{totp::SYSTEM_NOW, Algorithm, Totp};
+
+/// Compute the HMAC with SHA-1, for [`otp_totp_new`].
+pub const OTP_SHA1: u32 = 0;
+/// Compute the HMAC with SHA-256, for [`otp_totp_new`].
+pub const OTP_SHA256: u32 = 1;
+/// Compute the HMAC with SHA-512, for [`otp_totp_new`].
+pub const OTP_SHA512: u32 = 2;
+
+/// The result of a call through the C interface.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpStatus {
+    /// The call succeeded, or the submitted code was accepted.
+    Ok = 0,
+    /// A required pointer was null.
+    NullPointer = 1,
+    /// A string was not valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The secret, number of digits, step or algorithm was rejected.
+    InvalidArgument = 3,
+    /// The caller's buffer cannot hold the passcode and its terminator.
+    BufferTooSmall = 4,
+    /// The submitted code did not match.
+    Rejected = 5,
+    /// The passcode could not be computed.
+    Internal = 6,
+}
+
+/// An opaque TOTP generator, owned by the caller between
+/// [`otp_totp_new`] and [`otp_totp_free`].
+pub struct OtpTotp {
+    totp: Totp,
+}
+
+/// Create a generator of `digits` digit passcodes, in windows of `step`
+/// seconds from the Unix epoch, from a Base32-encoded `secret`.
+///
+/// `algorithm` is one of [`OTP_SHA1`], [`OTP_SHA256`] or
+/// [`OTP_SHA512`]. On success the handle is written to `out`.
+///
+/// # Safety
+///
+/// `secret` must be null or a NUL-terminated string, and `out` must be
+/// null or valid for writing a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn otp_totp_new(
+    secret: *const c_char,
+    digits: u32,
+    step: u64,
+    algorithm: u32,
+    out: *mut *mut OtpTotp,
+) -> OtpStatus {
+    if out.is_null() {
+        return OtpStatus::NullPointer;
+    }
+    // SAFETY: the caller guarantees `secret` is null or NUL-terminated.
+    let secret = match unsafe { c_str(secret) } {
+        Ok(secret) => secret,
+        Err(status) => return status,
+    };
+    let algorithm = match algorithm {
+        OTP_SHA1 => Algorithm::Sha1,
+        OTP_SHA256 => Algorithm::Sha256,
+        OTP_SHA512 => Algorithm::Sha512,
+        _ => return OtpStatus::InvalidArgument,
+    };
+    guard(|| {
+        let totp = crate::base32::decode(secret).and_then(|key| {
+            Totp::new_with_algorithm(key, Instant::at(0, 0), step, digits, algorithm, SYSTEM_NOW)
+        });
+        match totp {
+            Ok(totp) => {
+                // SAFETY: `out` is non-null, and the caller guarantees it
+                // is valid for writes.
+                unsafe { out.write(Box::into_raw(Box::new(OtpTotp { totp }))) };
+                OtpStatus::Ok
+            }
+            Err(_) => OtpStatus::InvalidArgument,
+        }
+    })
+}
+
+/// Write the passcode for the current window to `buf`, with leading
+/// zeroes and a NUL terminator.
+///
+/// `len` is the size of `buf` in bytes, which must be at least one more
+/// than the number of digits.
+///
+/// # Safety
+///
+/// `totp` must be null or a handle from [`otp_totp_new`] which has not
+/// been freed, and `buf` must be null or valid for writing `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn otp_totp_generate(
+    totp: *const OtpTotp,
+    buf: *mut c_char,
+    len: usize,
+) -> OtpStatus {
+    if totp.is_null() || buf.is_null() {
+        return OtpStatus::NullPointer;
+    }
+    // SAFETY: the caller guarantees `totp` is a live handle.
+    let totp = unsafe { &(*totp).totp };
+    guard(|| {
+        let code = match totp.get_at(Instant::now()) {
+            Ok(code) => totp.encoding.format(code, totp.width()),
+            Err(_) => return OtpStatus::Internal,
+        };
+        if code.len() >= len {
+            return OtpStatus::BufferTooSmall;
+        }
+        // SAFETY: `buf` is non-null and valid for `len` bytes, which
+        // leaves room for the code and its terminator.
+        unsafe {
+            ptr::copy_nonoverlapping(code.as_ptr(), buf.cast::<u8>(), code.len());
+            buf.add(code.len()).write(0);
+        }
+        OtpStatus::Ok
+    })
+}
+
+/// Check a submitted `code`, as typed by the user, against the
+/// passcodes for the current window and `skew` windows either side.
+///
+/// Returns [`OtpStatus::Ok`] if the code was accepted and
+/// [`OtpStatus::Rejected`] if not. As for [`Totp::verify_str`], leading
+/// zeroes must not be dropped.
+///
+/// # Safety
+///
+/// `totp` must be null or a handle from [`otp_totp_new`] which has not
+/// been freed, and `code` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn otp_totp_verify(
+    totp: *const OtpTotp,
+    code: *const c_char,
+    skew: u64,
+) -> OtpStatus {
+    if totp.is_null() {
+        return OtpStatus::NullPointer;
+    }
+    // SAFETY: the caller guarantees `totp` is a live handle.
+    let totp = unsafe { &(*totp).totp };
+    // SAFETY: the caller guarantees `code` is null or NUL-terminated.
+    let code = match unsafe { c_str(code) } {
+        Ok(code) => code,
+        Err(status) => return status,
+    };
+    guard(|| {
+        if totp.verify_str_with_skew(code, skew) {
+            OtpStatus::Ok
+        } else {
+            OtpStatus::Rejected
+        }
+    })
+}
+
+/// Free a handle from [`otp_totp_new`], wiping its secret. A null
+/// handle is ignored.
+///
+/// # Safety
+///
+/// `totp` must be null or a handle from [`otp_totp_new`] which has not
+/// already been freed. It must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn otp_totp_free(totp: *mut OtpTotp) {
+    if !totp.is_null() {
+        // SAFETY: the caller guarantees `totp` came from `Box::into_raw`
+        // in `otp_totp_new` and is freed only once.
+        drop(unsafe { Box::from_raw(totp) });
+    }
+}
+
+/// Borrow a C string as UTF-8.
+///
+/// # Safety
+///
+/// `s` must be null or a NUL-terminated string which outlives the
+/// borrow.
+unsafe fn c_str<'a>(s: *const c_char) -> Result<&'a str, OtpStatus> {
+    if s.is_null() {
+        return Err(OtpStatus::NullPointer);
+    }
+    // SAFETY: `s` is non-null, and the caller guarantees it is
+    // NUL-terminated.
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|_| OtpStatus::InvalidUtf8)
+}
+
+/// Run `f`, reporting a panic as [`OtpStatus::Internal`] rather than
+/// unwinding into the caller.
+fn guard(f: impl FnOnce() -> OtpStatus) -> OtpStatus {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(OtpStatus::Internal)
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::{c_char, CStr};
+    use std::ptr;
+
+    use test_case::test_case;
+
+    use crate::ffi::{
+        otp_totp_free, otp_totp_generate, otp_totp_new, otp_totp_verify, OtpStatus, OtpTotp,
+        OTP_SHA1, OTP_SHA256,
+    };
+
+    const SECRET: &CStr = c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn new(secret: &CStr, digits: u32, algorithm: u32) -> Result<*mut OtpTotp, OtpStatus> {
+        let mut totp = ptr::null_mut();
+        match unsafe { otp_totp_new(secret.as_ptr(), digits, 30, algorithm, &mut totp) } {
+            OtpStatus::Ok => Ok(totp),
+            status => Err(status),
+        }
+    }
+
+    #[test_case(OTP_SHA1)]
+    #[test_case(OTP_SHA256)]
+    fn it_generates_and_verifies_codes(algorithm: u32) {
+        let totp = new(SECRET, 8, algorithm).unwrap();
+        let mut buf = [0 as c_char; 9];
+        unsafe {
+            assert_eq!(
+                otp_totp_generate(totp, buf.as_mut_ptr(), buf.len()),
+                OtpStatus::Ok
+            );
+            let code = CStr::from_ptr(buf.as_ptr());
+            assert_eq!(code.to_bytes().len(), 8);
+            assert_eq!(otp_totp_verify(totp, code.as_ptr(), 1), OtpStatus::Ok);
+            assert_eq!(
+                otp_totp_verify(totp, c"1234567".as_ptr(), 1),
+                OtpStatus::Rejected
+            );
+            otp_totp_free(totp);
+        }
+    }
+
+    #[test_case(SECRET, 11, OTP_SHA1, OtpStatus::InvalidArgument ; "too many digits")]
+    #[test_case(SECRET, 6, 3, OtpStatus::InvalidArgument ; "unknown algorithm")]
+    #[test_case(c"not base32!", 6, OTP_SHA1, OtpStatus::InvalidArgument ; "bad secret")]
+    #[test_case(c"\xff", 6, OTP_SHA1, OtpStatus::InvalidUtf8 ; "invalid utf8")]
+    fn it_rejects_bad_configuration(secret: &CStr, digits: u32, algorithm: u32, status: OtpStatus) {
+        assert_eq!(new(secret, digits, algorithm), Err(status));
+    }
+
+    #[test]
+    fn it_handles_null_pointers() {
+        let mut totp = ptr::null_mut();
+        let mut buf = [0 as c_char; 7];
+        unsafe {
+            assert_eq!(
+                otp_totp_new(ptr::null(), 6, 30, OTP_SHA1, &mut totp),
+                OtpStatus::NullPointer
+            );
+            assert_eq!(
+                otp_totp_new(SECRET.as_ptr(), 6, 30, OTP_SHA1, ptr::null_mut()),
+                OtpStatus::NullPointer
+            );
+            assert_eq!(
+                otp_totp_generate(ptr::null(), buf.as_mut_ptr(), buf.len()),
+                OtpStatus::NullPointer
+            );
+            assert_eq!(
+                otp_totp_verify(ptr::null(), c"123456".as_ptr(), 0),
+                OtpStatus::NullPointer
+            );
+            otp_totp_free(ptr::null_mut());
+
+            let totp = new(SECRET, 6, OTP_SHA1).unwrap();
+            assert_eq!(
+                otp_totp_generate(totp, ptr::null_mut(), 7),
+                OtpStatus::NullPointer
+            );
+            assert_eq!(
+                otp_totp_verify(totp, ptr::null(), 0),
+                OtpStatus::NullPointer
+            );
+            otp_totp_free(totp);
+        }
+    }
+
+    #[test]
+    fn it_requires_room_for_the_terminator() {
+        let totp = new(SECRET, 6, OTP_SHA1).unwrap();
+        let mut buf = [0 as c_char; 6];
+        unsafe {
+            assert_eq!(
+                otp_totp_generate(totp, buf.as_mut_ptr(), buf.len()),
+                OtpStatus::BufferTooSmall
+            );
+            otp_totp_free(totp);
+        }
+    }
+}
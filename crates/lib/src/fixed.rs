@@ -0,0 +1,170 @@
+//! Generators whose number of digits is fixed by their type, for code
+//! which must never be configured with the wrong passcode length.
+
+use alloc::{format, string::String, vec::Vec};
+use core::ops::Deref;
+
+#[cfg(feature = "std")]
+use unix_time::Instant;
+
+use crate::{hotp::Counter, Hotp, Otp, OtpResult, ToBytes, MAX_DIGITS};
+#[cfg(feature = "std")]
+use crate::{totp::Time, Totp};
+
+/// A generator of passcodes of exactly `DIGITS` decimal digits.
+///
+/// A number of digits which is not between 1 and [`MAX_DIGITS`] fails
+/// to compile, rather than failing when the generator is constructed:
+///
+/// ```compile_fail
+/// use otp2::FixedTotp;
+///
+/// let totp = FixedTotp::<11>::new("12345678901234567890", unix_time::Instant::at(0, 0), 30);
+/// ```
+///
+/// The runtime-configured generator is available through [`Deref`], e.g.
+/// for [`Totp::verify`].
+#[derive(Debug, Clone)]
+pub struct FixedOtp<G: ToBytes, const DIGITS: u32> {
+    otp: Otp<G>,
+}
+
+/// A TOTP generator of passcodes of exactly `DIGITS` digits.
+#[cfg(feature = "std")]
+pub type FixedTotp<const DIGITS: u32> = FixedOtp<Time, DIGITS>;
+
+/// A HOTP generator of passcodes of exactly `DIGITS` digits.
+pub type FixedHotp<const DIGITS: u32> = FixedOtp<Counter, DIGITS>;
+
+/// A TOTP generator of 6 digit passcodes, as used by most
+/// authenticator apps.
+#[cfg(feature = "std")]
+pub type Totp6 = FixedTotp<6>;
+
+/// A TOTP generator of 8 digit passcodes.
+#[cfg(feature = "std")]
+pub type Totp8 = FixedTotp<8>;
+
+/// A HOTP generator of 6 digit passcodes.
+pub type Hotp6 = FixedHotp<6>;
+
+/// A HOTP generator of 8 digit passcodes.
+pub type Hotp8 = FixedHotp<8>;
+
+impl<G: ToBytes, const DIGITS: u32> FixedOtp<G, DIGITS> {
+    /// The number of distinct passcodes, which also checks `DIGITS` when
+    /// a generator is constructed.
+    const MODULUS: u32 = {
+        assert!(
+            DIGITS >= 1 && DIGITS <= MAX_DIGITS,
+            "passcodes must have between 1 and MAX_DIGITS digits"
+        );
+        // 10 digit passcodes are any truncated value.
+        match 10u32.checked_pow(DIGITS) {
+            Some(modulus) => modulus,
+            None => u32::MAX,
+        }
+    };
+
+    fn from_otp(otp: Otp<G>) -> Self {
+        debug_assert_eq!(otp.digits, DIGITS);
+        FixedOtp { otp }
+    }
+
+    /// Generate a one-time passcode, as by [`Otp::get`].
+    pub fn get(&mut self) -> OtpResult<u32> {
+        let c = self.otp.generator.to_bytes()?;
+        Ok(self.otp.truncate(c.as_ref())? % Self::MODULUS)
+    }
+
+    /// Generate a one-time passcode, formatted with leading zeroes to
+    /// exactly `DIGITS` digits.
+    pub fn get_string(&mut self) -> OtpResult<String> {
+        Ok(format!("{:0width$}", self.get()?, width = DIGITS as usize))
+    }
+
+    /// The runtime-configured generator.
+    pub fn into_inner(self) -> Otp<G> {
+        self.otp
+    }
+}
+
+impl<G: ToBytes, const DIGITS: u32> Deref for FixedOtp<G, DIGITS> {
+    type Target = Otp<G>;
+
+    fn deref(&self) -> &Otp<G> {
+        &self.otp
+    }
+}
+
+#[cfg(feature = "std")]
+impl<const DIGITS: u32> FixedTotp<DIGITS> {
+    /// Get a TOTP generator, as by [`Totp::new`].
+    ///
+    /// Fails if `step` is zero.
+    pub fn new(key: impl Into<Vec<u8>>, t0: Instant, step: u64) -> OtpResult<Self> {
+        let _ = Self::MODULUS;
+        Ok(FixedOtp::from_otp(Totp::new(key, t0, step, DIGITS)?))
+    }
+}
+
+impl<const DIGITS: u32> FixedHotp<DIGITS> {
+    /// Get a HOTP generator, as by [`Hotp::new`].
+    pub fn new(key: impl Into<Vec<u8>>, initial_count: u64) -> OtpResult<Self> {
+        let _ = Self::MODULUS;
+        Ok(FixedOtp::from_otp(Hotp::new(key, initial_count, DIGITS)?))
+    }
+
+    /// Check a submitted `code` as by [`Hotp::verify`], advancing the
+    /// counter on a match.
+    pub fn verify(&mut self, code: u32, look_ahead: u64) -> Option<u64> {
+        self.otp.verify(code, look_ahead)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+    #[cfg(feature = "std")]
+    use unix_time::Instant;
+
+    use crate::{FixedHotp, Hotp, Hotp6};
+    #[cfg(feature = "std")]
+    use crate::{Totp, Totp8};
+
+    #[test]
+    fn it_computes_the_rfc_4226_vectors() {
+        let mut hotp = Hotp6::new("12345678901234567890", 0).unwrap();
+        assert_eq!(hotp.get_string().unwrap(), "755224");
+        assert_eq!(hotp.get().unwrap(), 287082);
+        assert_eq!(hotp.count(), 2);
+        assert_eq!(hotp.verify(969429, 1), Some(3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn it_computes_the_rfc_6238_vectors() {
+        let mut totp = Totp8::new("12345678901234567890", Instant::at(0, 0), 30).unwrap();
+        assert_eq!(totp.get_at(Instant::at(59, 0)).unwrap(), 94287082);
+        assert!(totp.get_string().is_ok_and(|code| code.len() == 8));
+    }
+
+    // Every truncated value is a valid 10 digit passcode.
+    #[test_case(0)]
+    #[test_case(1)]
+    #[test_case(9)]
+    fn it_matches_the_runtime_digits(counter: u64) {
+        let key = "12345678901234567890";
+        let mut runtime = Hotp::new(key, counter, 10).unwrap();
+        let mut fixed = FixedHotp::<10>::new(key, counter).unwrap();
+        assert_eq!(fixed.get_string().unwrap(), runtime.get_string().unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn it_unwraps_the_runtime_generator() {
+        let totp = Totp8::new("12345678901234567890", Instant::at(0, 0), 30).unwrap();
+        let totp: Totp = totp.into_inner();
+        assert_eq!(totp.get_at(Instant::at(59, 0)).unwrap(), 94287082);
+    }
+}
@@ -0,0 +1,148 @@
+//! One-time passcodes with an HMAC over any digest.
+//!
+//! [`Hotp`] and [`Totp`] are not aliases of `HmacOtp<Sha1>`, as was first
+//! proposed: they choose among the three digests of
+//! [`Algorithm`](crate::Algorithm) at runtime, so that an `algorithm`
+//! read from a provisioning URI or config doesn't change their type.
+//! Instead [`HmacOtp`] builds the same generators with their HMAC computed
+//! over a digest fixed at compile time, which need not be one of those
+//! three.
+
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use hmac::digest::{core_api::BlockSizeUser, Digest};
+#[cfg(feature = "std")]
+use unix_time::Instant;
+use zeroize::Zeroizing;
+
+use crate::{check_digits, hotp::Counter, Encoding, Hotp, Key, Otp, OtpResult, ToBytes};
+#[cfg(feature = "std")]
+use crate::{
+    totp::{Time, SYSTEM_NOW},
+    Totp,
+};
+
+/// A shared secret for passcodes whose HMAC is computed over the digest
+/// `D`, rather than one of the three of [`Algorithm`](crate::Algorithm).
+///
+/// Any digest implementing the `digest` crate's traits can be used, e.g.
+/// BLAKE2 or SM3. The passcodes come from the same generators as usual,
+/// and the dynamic truncation works for digests of any length. Since other
+/// apps won't recognise the digest, these generators can't be exported to
+/// provisioning URIs or configs.
+///
+/// ```
+/// use blake2::Blake2s256;
+/// use otp2::HmacOtp;
+///
+/// let mut hotp = HmacOtp::<Blake2s256>::new(b"12345678901234567890").hotp(0, 6)?;
+/// assert_eq!(hotp.get_string()?, "054669");
+/// assert_eq!(hotp.get_string()?, "899568");
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+pub struct HmacOtp<D> {
+    key: Zeroizing<Vec<u8>>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> HmacOtp<D>
+where
+    D: Digest + BlockSizeUser + Clone + Send + Sync + 'static,
+{
+    /// Use `key` as the secret. The `key` is an arbitrary byte string and
+    /// need not be valid UTF-8.
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        HmacOtp {
+            key: Zeroizing::new(key.into()),
+            digest: PhantomData,
+        }
+    }
+
+    /// Get a HOTP generator, as by [`Hotp::new`].
+    pub fn hotp(self, initial_count: u64, length: u32) -> OtpResult<Hotp> {
+        self.from_generator(Counter::new(initial_count), length)
+    }
+
+    /// Get a TOTP generator, as by [`Totp::new`].
+    #[cfg(feature = "std")]
+    pub fn totp(self, t0: Instant, window: u64, length: u32) -> OtpResult<Totp> {
+        let generator = Time::new(t0, window, SYSTEM_NOW)?;
+        self.from_generator(generator, length)
+    }
+
+    /// Get a generator which computes passcodes of `digits` from the
+    /// bytes of a custom `generator`, as by [`Otp::from_generator`].
+    pub fn from_generator<G: ToBytes>(self, generator: G, digits: u32) -> OtpResult<Otp<G>> {
+        check_digits(digits)?;
+        Ok(Otp {
+            key: Key::with_digest::<D>(self.key.to_vec()),
+            generator,
+            digits,
+            checksum: false,
+            encoding: Encoding::decimal(),
+            issuer: None,
+            account: None,
+        })
+    }
+}
+
+/// Never print the secret itself.
+impl<D> fmt::Debug for HmacOtp<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacOtp")
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use blake2::{Blake2b512, Blake2s256};
+    use sha1::Sha1;
+    use test_case::test_case;
+
+    use crate::{HmacOtp, Hotp, HotpError};
+
+    const KEY: &[u8] = b"12345678901234567890";
+
+    #[test_case(0 => 755224)]
+    #[test_case(1 => 287082)]
+    #[test_case(9 => 520489)]
+    fn it_matches_the_rfc_with_sha1(counter: u64) -> u32 {
+        HmacOtp::<Sha1>::new(KEY)
+            .hotp(counter, 6)
+            .unwrap()
+            .get()
+            .unwrap()
+    }
+
+    // Computed with Python's `hmac` and `hashlib` modules.
+    #[test_case(0 => 48054669 ; "blake2s 0")]
+    #[test_case(1 => 46899568 ; "blake2s 1")]
+    fn it_truncates_32_byte_digests(counter: u64) -> u32 {
+        let mut hotp = HmacOtp::<Blake2s256>::new(KEY).hotp(counter, 8).unwrap();
+        hotp.get().unwrap()
+    }
+
+    #[test_case(0 => 85737565 ; "blake2b 0")]
+    #[test_case(1 => 10409498 ; "blake2b 1")]
+    fn it_truncates_longer_digests(counter: u64) -> u32 {
+        let mut hotp = HmacOtp::<Blake2b512>::new(KEY).hotp(counter, 8).unwrap();
+        hotp.get().unwrap()
+    }
+
+    #[test]
+    fn it_refuses_to_export_other_digests() {
+        let hotp = HmacOtp::<Blake2s256>::new(KEY).hotp(0, 6).unwrap();
+        assert!(matches!(
+            hotp.to_uri("ACME", "alice"),
+            Err(HotpError::NonStandardDigest)
+        ));
+        // The secret is still known, unlike with an external HMAC.
+        assert_eq!(
+            hotp.key_fingerprint().unwrap(),
+            Hotp::new(KEY, 0, 6).unwrap().key_fingerprint().unwrap()
+        );
+    }
+}
@@ -1,4 +1,16 @@
-use crate::{Otp, OtpResult, ToBytes};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::fmt;
+
+use zeroize::Zeroizing;
+
+use crate::{
+    base32, check_digits, check_key_length, find_match, uri, Algorithm, Encoding, HotpError, Key,
+    MacFn, Otp, OtpResult, ToBytes, MAX_CHECKSUM_DIGITS,
+};
 
 /// HMAC-based one-time passcode
 ///
@@ -9,36 +21,405 @@ pub type Hotp = Otp<Counter>;
 impl Hotp {
     /// Get a HOTP generator with the given `key`, initial count, and which
     /// generates passcodes of `length`.
-    pub fn new(key: String, initial_count: u64, length: u32) -> Self {
-        Otp {
-            key,
-            generator: Counter {
-                count: initial_count,
-            },
+    ///
+    /// The `key` is an arbitrary byte string and need not be valid UTF-8.
+    /// Fails if `length` is not between 1 and [`MAX_DIGITS`](crate::MAX_DIGITS).
+    pub fn new(key: impl Into<Vec<u8>>, initial_count: u64, length: u32) -> OtpResult<Self> {
+        Hotp::new_with_algorithm(key, initial_count, length, Algorithm::Sha1)
+    }
+
+    /// Get a HOTP generator, rejecting keys shorter than
+    /// [`MIN_KEY_LENGTH`](crate::MIN_KEY_LENGTH) bytes.
+    ///
+    /// See [`Self::new`].
+    pub fn new_strict(key: impl Into<Vec<u8>>, initial_count: u64, length: u32) -> OtpResult<Self> {
+        let key = key.into();
+        check_key_length(&key)?;
+        Hotp::new(key, initial_count, length)
+    }
+
+    /// Get a HOTP generator from a Base32-encoded `secret`, as found in
+    /// provisioning URIs and authenticator apps.
+    ///
+    /// See [`Self::new`].
+    pub fn from_base32(secret: &str, initial_count: u64, length: u32) -> OtpResult<Self> {
+        let key = base32::decode(secret)?;
+        Hotp::new(key, initial_count, length)
+    }
+
+    /// Get a HOTP generator from a hex-encoded `secret`, as the RFC
+    /// appendices express it.
+    ///
+    /// See [`Self::new`].
+    pub fn from_hex(secret: &str, initial_count: u64, length: u32) -> OtpResult<Self> {
+        let key = hex::decode(secret)?;
+        Hotp::new(key, initial_count, length)
+    }
+
+    /// Get a HOTP generator from an `otpauth://hotp/` provisioning URI,
+    /// as scanned from an enrollment QR code. The issuer and account
+    /// are kept, see [`Self::issuer`] and [`Self::account`].
+    ///
+    /// The digits and algorithm default to 6 and SHA-1 when the URI does
+    /// not give them, but the counter is required. Fails if the URI is
+    /// malformed or is not for HOTP.
+    pub fn from_uri(uri: &str) -> OtpResult<Self> {
+        let parsed = uri::parse(uri, "hotp")?;
+        let counter = parsed.counter.ok_or_else(|| HotpError::InvalidUri {
+            err: "missing counter".to_string(),
+        })?;
+        let mut hotp =
+            Hotp::new_with_algorithm(parsed.secret, counter, parsed.digits, parsed.algorithm)?;
+        hotp.issuer = parsed.issuer;
+        hotp.account = Some(parsed.account);
+        Ok(hotp)
+    }
+
+    /// Get a HOTP generator which computes the HMAC using `algorithm`.
+    ///
+    /// See [`Self::new`].
+    pub fn new_with_algorithm(
+        key: impl Into<Vec<u8>>,
+        initial_count: u64,
+        length: u32,
+        algorithm: Algorithm,
+    ) -> OtpResult<Self> {
+        Otp::from_generator(key, Counter::new(initial_count), length, algorithm)
+    }
+
+    /// Get a HOTP generator which delegates computing the HMAC to `mac`,
+    /// so that the secret key never has to be given to this crate.
+    ///
+    /// See [`Self::new`].
+    pub fn new_with_mac(mac: MacFn, initial_count: u64, length: u32) -> OtpResult<Self> {
+        check_digits(length)?;
+        Ok(Otp {
+            key: Key::External(Arc::from(mac)),
+            generator: Counter::new(initial_count),
             digits: length,
+            checksum: false,
+            encoding: Encoding::decimal(),
+            issuer: None,
+            account: None,
+        })
+    }
+
+    /// The counter the next passcode will be computed from, which should
+    /// be persisted so that a restarted service continues from it.
+    pub fn count(&self) -> u64 {
+        self.generator.count
+    }
+
+    /// Generate `n` consecutive passcodes, formatted as by
+    /// [`Self::get_string`], e.g. for a printed list of backup codes.
+    ///
+    /// The counter advances by `n`. Fails on the first passcode that
+    /// cannot be generated.
+    pub fn generate_batch(&mut self, n: usize) -> OtpResult<Vec<String>> {
+        (0..n).map(|_| self.get_string()).collect()
+    }
+
+    /// Capture the generator's mutable state, i.e. its counter, to be
+    /// persisted separately from the secret.
+    pub fn save_state(&self) -> HotpState {
+        HotpState {
+            count: self.generator.count,
         }
     }
+
+    /// Restore state captured by [`Self::save_state`], possibly from
+    /// another generator with the same secret.
+    pub fn restore_state(&mut self, state: HotpState) {
+        self.generator.count = state.count;
+    }
+
+    /// Return the counter to the value the generator was constructed
+    /// with.
+    pub fn reset(&mut self) {
+        self.generator.count = self.generator.initial;
+    }
+
+    /// Compute the passcode for the current counter without advancing
+    /// it, unlike [`Self::get`].
+    pub fn peek(&self) -> OtpResult<u32> {
+        self.get_at_counter(self.generator.count)
+    }
+
+    /// Compute the passcode for an arbitrary `counter`, without touching
+    /// the generator's own counter. This is the HOTP analogue of
+    /// `Totp::get_at`.
+    pub fn get_at_counter(&self, counter: u64) -> OtpResult<u32> {
+        self.compute(&counter.to_be_bytes())
+    }
+
+    /// Set the counter the next passcode will be computed from, e.g. to
+    /// restore one persisted from [`Self::count`].
+    pub fn set_count(&mut self, count: u64) {
+        self.generator.count = count;
+    }
+
+    /// Check a submitted `code` against the passcodes for the current
+    /// counter and up to `look_ahead` counters after it.
+    ///
+    /// This is the resynchronization of RFC 4226 section 7.4: on a match
+    /// the counter is advanced past the matching counter, which is
+    /// returned. Returns `None`, leaving the counter untouched, if no
+    /// counter in the window matches. Each comparison is constant-time.
+    ///
+    /// As [`Self::get`] never generates the passcode for `u64::MAX`, it
+    /// is never accepted either.
+    pub fn verify(&mut self, code: u32, look_ahead: u64) -> Option<u64> {
+        let matched = self.find_in_window(code, look_ahead)?;
+        self.generator.count = matched + 1;
+        Some(matched)
+    }
+
+    /// Check a submitted code, as typed by the user, as by
+    /// [`Self::verify`].
+    ///
+    /// Inputs which aren't exactly as many decimal digits as the passcodes
+    /// are rejected, so that e.g. `"12345"` is not taken for `"012345"`.
+    pub fn verify_str(&mut self, input: &str, look_ahead: u64) -> Option<u64> {
+        let code = self.parse_code(input)?;
+        self.verify(code, look_ahead)
+    }
+
+    /// Check a submitted `code` against the same window as
+    /// [`Self::verify`], but without advancing the counter on a match,
+    /// e.g. for a dry run.
+    pub fn check(&self, code: u32, look_ahead: u64) -> bool {
+        self.find_in_window(code, look_ahead).is_some()
+    }
+
+    /// Write the `otpauth://hotp/` provisioning URI for this generator,
+    /// which authenticator apps can scan as a QR code to enroll it.
+    ///
+    /// The URI carries the current counter, without advancing it, so
+    /// that the app starts in sync. Fails if the HMAC is computed
+    /// externally, since the secret is then unknown, or if the passcodes
+    /// have a checksum digit, which authenticator apps don't compute.
+    pub fn to_uri(&self, issuer: &str, account: &str) -> OtpResult<String> {
+        let mut builder = self.uri_builder("hotp", issuer, account)?;
+        builder.param("counter", &self.generator.count.to_string());
+        Ok(builder.build())
+    }
+
+    /// Write the provisioning URI as by [`Self::to_uri`], labelled with
+    /// the generator's own issuer and account, e.g. those read by
+    /// [`Self::from_uri`].
+    pub fn to_uri_with_metadata(&self) -> OtpResult<String> {
+        self.to_uri(
+            self.issuer().unwrap_or_default(),
+            self.account().unwrap_or_default(),
+        )
+    }
+
+    /// The first counter from the current one to `look_ahead` after it
+    /// whose passcode is `code`, comparing every counter in the window so
+    /// as not to reveal which matched.
+    fn find_in_window(&self, code: u32, look_ahead: u64) -> Option<u64> {
+        let first = self.generator.count;
+        let last = first.saturating_add(look_ahead).min(u64::MAX - 1);
+        find_match(
+            code,
+            (first..=last).map(|counter| (counter, self.get_at_counter(counter))),
+        )
+    }
+}
+
+/// The state of a [`Hotp`] generator which must survive a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HotpState {
+    /// The counter the next passcode will be computed from.
+    pub count: u64,
 }
 
 /// The backing type which implements the [`ToBytes`] interface,
 /// using a counter to generate the value bytes.
+///
+/// The counter can't advance past `u64::MAX`, so no passcode is
+/// generated for it and [`HotpError::CounterExhausted`] is returned
+/// instead.
+#[derive(Debug, Clone)]
 pub struct Counter {
     count: u64,
+    /// The count the generator was constructed with, for [`Hotp::reset`].
+    initial: u64,
+}
+
+impl Counter {
+    pub(crate) fn new(initial: u64) -> Self {
+        Counter {
+            count: initial,
+            initial,
+        }
+    }
 }
 
 impl ToBytes for Counter {
+    type Bytes = [u8; 8];
+
     fn to_bytes(&mut self) -> OtpResult<[u8; 8]> {
         let c = self.count;
-        self.count += 1;
-        Ok(c.to_be_bytes().into())
+        self.count = c.checked_add(1).ok_or(HotpError::CounterExhausted)?;
+        Ok(c.to_be_bytes())
+    }
+}
+
+/// A sequence of passcodes, advancing the counter as by [`Hotp::get`].
+/// It only ends if the counter is exhausted.
+///
+/// This is implemented for `&mut Hotp` rather than `Hotp`, so iterate
+/// with `(&mut hotp).take(10)` rather than `hotp.by_ref().take(10)`. On
+/// `Hotp` itself, [`Iterator::count`] would be picked over
+/// [`Hotp::count`] and consume the generator.
+///
+/// ```
+/// use otp2::Hotp;
+///
+/// let mut hotp = Hotp::new("12345678901234567890", 0, 6)?;
+/// let codes = (&mut hotp).take(3).collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(codes, [755224, 287082, 359152]);
+/// assert_eq!(hotp.count(), 3);
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+impl Iterator for &mut Hotp {
+    type Item = OtpResult<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.get() {
+            Err(HotpError::CounterExhausted) => None,
+            result => Some(result),
+        }
+    }
+}
+
+/// Displays the passcode for the current counter, zero-padded to the
+/// configured number of digits.
+///
+/// Unlike [`Hotp::get`], displaying the passcode does not increment
+/// the counter.
+impl fmt::Display for Hotp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_code(&self.generator.count.to_be_bytes(), f)
+    }
+}
+
+/// Builds a [`Hotp`] generator one option at a time, as an alternative
+/// to the positional constructors.
+///
+/// Only the secret is required. Otherwise the defaults are 6 digit
+/// SHA-1 passcodes starting from a counter of 0.
+///
+/// ```
+/// use otp2::HotpBuilder;
+///
+/// let mut hotp = HotpBuilder::new()
+///     .secret("12345678901234567890")
+///     .counter(1)
+///     .build()
+///     .unwrap();
+/// assert_eq!(hotp.get().unwrap(), 287082);
+/// ```
+#[derive(Default)]
+pub struct HotpBuilder {
+    secret: Option<Zeroizing<Vec<u8>>>,
+    counter: u64,
+    digits: Option<u32>,
+    checksum: bool,
+    algorithm: Algorithm,
+    issuer: Option<String>,
+    account: Option<String>,
+}
+
+impl HotpBuilder {
+    /// Start building a generator with the default options.
+    pub fn new() -> Self {
+        HotpBuilder::default()
+    }
+
+    /// The shared secret, as raw bytes.
+    pub fn secret(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.secret = Some(Zeroizing::new(key.into()));
+        self
+    }
+
+    /// The counter used for the first passcode.
+    pub fn counter(mut self, counter: u64) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// The number of digits in each passcode.
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.digits = Some(digits);
+        self
+    }
+
+    /// Append the checksum digit of RFC 4226 section 7.3 to each
+    /// passcode, so that a mistyped code can be told apart from a wrong
+    /// one. Passcodes are then one digit longer than [`Self::digits`].
+    ///
+    /// Authenticator apps do not compute the checksum, so this is only
+    /// for codes delivered by other means, e.g. printed or sent by SMS.
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// The hash algorithm used to compute the HMAC.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// See [`Hotp::with_issuer`](crate::Otp::with_issuer).
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// See [`Hotp::with_account`](crate::Otp::with_account).
+    pub fn account(mut self, account: impl Into<String>) -> Self {
+        self.account = Some(account.into());
+        self
+    }
+
+    /// Build the generator.
+    ///
+    /// Fails if no secret was given, or as [`Hotp::new`] does for an
+    /// invalid number of digits. With a checksum digit, there may be at
+    /// most [`MAX_CHECKSUM_DIGITS`](crate::MAX_CHECKSUM_DIGITS) others.
+    pub fn build(self) -> OtpResult<Hotp> {
+        let mut secret = self.secret.ok_or(HotpError::MissingSecret)?;
+        let digits = self.digits.unwrap_or(6);
+        if self.checksum && digits > MAX_CHECKSUM_DIGITS {
+            return Err(HotpError::InvalidChecksumDigits { digits });
+        }
+        let mut hotp = Hotp::new_with_algorithm(
+            core::mem::take(&mut *secret),
+            self.counter,
+            digits,
+            self.algorithm,
+        )?;
+        hotp.checksum = self.checksum;
+        hotp.issuer = self.issuer;
+        hotp.account = self.account;
+        Ok(hotp)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use hmac::{Hmac, Mac};
+    use sha1::Sha1;
     use test_case::test_case;
 
-    use crate::hotp::Hotp;
+    use crate::{
+        hotp::{Hotp, HotpBuilder, HotpState},
+        Algorithm, HotpError,
+    };
 
     // These test cases are copied from RFC 4226
     // https://datatracker.ietf.org/doc/html/rfc4226#appendix-D
@@ -55,11 +436,22 @@ mod test {
     fn it_computes_correct_hotp(counter: u64, expected: u32) {
         let key = "12345678901234567890".to_string();
         let digits = 6;
-        let mut hotp = Hotp::new(key, counter, digits);
+        let mut hotp = Hotp::new(key, counter, digits).unwrap();
         let actual = hotp.get().unwrap();
         assert_eq!(actual, expected);
     }
 
+    // The truncated values from RFC 4226 appendix D are exactly 10 digits
+    // or fewer, so longer codes are the values themselves.
+    #[test_case(0, 9, 284755224)]
+    #[test_case(0, 10, 1284755224)]
+    #[test_case(3, 10, 1726969429)]
+    #[test_case(7, 10, 82162583)]
+    fn it_computes_long_hotp(counter: u64, digits: u32, expected: u32) {
+        let mut hotp = Hotp::new("12345678901234567890", counter, digits).unwrap();
+        assert_eq!(hotp.get().unwrap(), expected);
+    }
+
     #[test]
     fn it_increments_the_counter() {
         let cases = vec![
@@ -68,10 +460,458 @@ mod test {
         let key = "12345678901234567890".to_string();
         let digits = 6;
         let counter = 0;
-        let mut htop = Hotp::new(key, counter, digits);
+        let mut htop = Hotp::new(key, counter, digits).unwrap();
         for case in cases {
             let actual = htop.get().unwrap();
             assert_eq!(actual, case);
         }
     }
+
+    #[test]
+    fn it_fingerprints_the_secret() {
+        let fingerprint = |key: &str| Hotp::new(key, 0, 6).unwrap().key_fingerprint().unwrap();
+        let a = fingerprint("12345678901234567890");
+        assert_eq!(a, "6ed645ef0e1abea1");
+        assert_eq!(a, fingerprint("12345678901234567890"));
+        assert_eq!(
+            a,
+            Hotp::new("12345678901234567890", 5, 8)
+                .unwrap()
+                .key_fingerprint()
+                .unwrap()
+        );
+        assert_ne!(a, fingerprint("12345678901234567891"));
+
+        let mac = Box::new(|_: &[u8]| Ok(vec![0; 20]));
+        let hotp = Hotp::new_with_mac(mac, 0, 6).unwrap();
+        assert!(matches!(
+            hotp.key_fingerprint(),
+            Err(HotpError::SecretUnavailable)
+        ));
+    }
+
+    #[test]
+    fn it_uses_an_external_mac() {
+        let mac = Box::new(|counter: &[u8]| {
+            let mut mac = Hmac::<Sha1>::new_from_slice(b"12345678901234567890")?;
+            mac.update(counter);
+            Ok(mac.finalize().into_bytes().to_vec())
+        });
+        let mut hotp = Hotp::new_with_mac(mac, 0, 6).unwrap();
+        assert_eq!(hotp.get().unwrap(), 755224);
+        assert_eq!(hotp.get().unwrap(), 287082);
+    }
+
+    #[test]
+    fn it_rejects_a_truncated_external_mac() {
+        let mac = Box::new(|_: &[u8]| Ok(vec![0x0f; 8]));
+        let mut hotp = Hotp::new_with_mac(mac, 0, 6).unwrap();
+        assert!(matches!(hotp.get(), Err(HotpError::Truncation { len: 8 })));
+    }
+
+    #[test]
+    fn it_accepts_non_utf8_keys() {
+        let key = vec![0xff; 20];
+        assert!(String::from_utf8(key.clone()).is_err());
+        let mac = Box::new(move |counter: &[u8]| {
+            let mut mac = Hmac::<Sha1>::new_from_slice(&[0xff; 20])?;
+            mac.update(counter);
+            Ok(mac.finalize().into_bytes().to_vec())
+        });
+        let mut expected = Hotp::new_with_mac(mac, 0, 6).unwrap();
+        let mut hotp = Hotp::new(key, 0, 6).unwrap();
+        assert_eq!(hotp.get().unwrap(), expected.get().unwrap());
+    }
+
+    #[test]
+    fn it_decodes_a_base32_secret() {
+        let key = "12345678901234567890".to_string();
+        let mut expected = Hotp::new(key, 0, 6).unwrap();
+        let mut hotp = Hotp::from_base32("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", 0, 6).unwrap();
+        for _ in 0..10 {
+            assert_eq!(hotp.get().unwrap(), expected.get().unwrap());
+        }
+    }
+
+    #[test]
+    fn it_rejects_an_invalid_base32_secret() {
+        assert!(Hotp::from_base32("GEZDGNBVGY3TQOJQ0", 0, 6).is_err());
+    }
+
+    #[test]
+    fn it_decodes_a_hex_secret() {
+        let key = "12345678901234567890".to_string();
+        let mut expected = Hotp::new(key, 0, 6).unwrap();
+        let secret = "3132333435363738393031323334353637383930";
+        let mut hotp = Hotp::from_hex(secret, 0, 6).unwrap();
+        for _ in 0..10 {
+            assert_eq!(hotp.get().unwrap(), expected.get().unwrap());
+        }
+    }
+
+    #[test_case("313"; "odd length")]
+    #[test_case("31zz"; "non-hex characters")]
+    fn it_rejects_an_invalid_hex_secret(secret: &str) {
+        assert!(Hotp::from_hex(secret, 0, 6).is_err());
+    }
+
+    #[test]
+    fn it_rejects_weak_keys_when_strict() {
+        let result = Hotp::new_strict("123456789012345", 0, 6);
+        assert!(matches!(result, Err(HotpError::WeakKey { length: 15 })));
+        assert!(Hotp::new_strict("1234567890123456", 0, 6).is_ok());
+    }
+
+    #[test]
+    fn it_redacts_the_key_when_debug_formatted() {
+        let hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        let formatted = format!("{hotp:?}");
+        assert!(formatted.contains("<redacted>"));
+        assert!(formatted.contains("digits: 6"));
+        assert!(formatted.contains("Sha1"));
+        assert!(!formatted.contains("12345678901234567890"));
+        assert!(!formatted.contains("49, 50, 51"));
+    }
+
+    #[test]
+    fn it_pads_long_codes() {
+        let mut hotp = Hotp::new("12345678901234567890", 7, 10).unwrap();
+        assert_eq!(hotp.get_string().unwrap(), "0082162583");
+    }
+
+    #[test]
+    fn it_displays_the_current_code_without_incrementing() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        assert_eq!(hotp.to_string(), "755224");
+        assert_eq!(hotp.to_string(), "755224");
+        assert_eq!(hotp.get().unwrap(), 755224);
+        assert_eq!(hotp.to_string(), "287082");
+    }
+
+    #[test_case(0)]
+    #[test_case(11)]
+    fn it_rejects_invalid_digits(digits: u32) {
+        let result = Hotp::new("12345678901234567890", 0, digits);
+        assert!(matches!(result, Err(HotpError::InvalidDigits { digits: d }) if d == digits));
+    }
+
+    #[test]
+    fn it_accepts_valid_digits() {
+        assert!(Hotp::new("12345678901234567890", 0, 6).is_ok());
+    }
+
+    #[test]
+    fn it_encodes_codes_over_a_custom_alphabet() {
+        let hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        let mut hotp = hotp
+            .with_alphabet("0123456789abcdefghijklmnopqrstuvwxyz")
+            .unwrap();
+        // The truncated value 1284755224 from RFC 4226 appendix D is
+        // "l8wrh4" in base 36.
+        assert_eq!(hotp.get_string().unwrap(), "l8wrh4");
+    }
+
+    #[test_case(""; "no symbols")]
+    #[test_case("0"; "one symbol")]
+    fn it_rejects_alphabets_with_fewer_than_two_symbols(alphabet: &str) {
+        let hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        let result = hotp.with_alphabet(alphabet);
+        assert!(matches!(result, Err(HotpError::InvalidAlphabet { .. })));
+    }
+
+    #[test]
+    fn it_writes_codes_into_a_buffer() {
+        let key = "12345678901234567890";
+        let mut expected = Hotp::new(key, 0, 6).unwrap();
+        let mut hotp = Hotp::new(key, 0, 6).unwrap();
+        let mut buf = [0; 6];
+        for _ in 0..10 {
+            let code = hotp.get_into(&mut buf).unwrap();
+            assert_eq!(code, expected.get_string().unwrap());
+        }
+    }
+
+    #[test]
+    fn it_writes_leading_zeroes_into_a_buffer() {
+        let mut hotp = Hotp::new("12345678901234567890", 7, 10).unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(hotp.get_into(&mut buf).unwrap(), "0082162583");
+        assert_eq!(&buf[..10], b"0082162583");
+    }
+
+    #[test]
+    fn it_rejects_a_buffer_shorter_than_the_code() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        let mut buf = [0; 5];
+        let result = hotp.get_into(&mut buf);
+        assert!(matches!(
+            result,
+            Err(HotpError::BufferTooSmall { needed: 6, len: 5 })
+        ));
+    }
+
+    #[test]
+    fn it_verifies_and_resynchronizes_the_counter() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        // The code for counter 3, which is within the look-ahead window
+        assert_eq!(hotp.verify(969429, 5), Some(3));
+        assert_eq!(hotp.to_string(), "338314");
+        // Codes at or behind the resynchronized counter are rejected
+        assert_eq!(hotp.verify(969429, 5), None);
+        assert_eq!(hotp.to_string(), "338314");
+    }
+
+    #[test_case("969429" => Some(3) ; "within the look-ahead")]
+    #[test_case("96942" => None ; "too short")]
+    #[test_case("0969429" => None ; "too long")]
+    #[test_case("+69429" => None ; "sign")]
+    #[test_case("969 29" => None ; "space")]
+    fn it_verifies_typed_codes(input: &str) -> Option<u64> {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        hotp.verify_str(input, 5)
+    }
+
+    #[test]
+    fn it_rejects_codes_beyond_the_look_ahead() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        assert_eq!(hotp.verify(520489, 8), None);
+        assert_eq!(hotp.verify(520489, 9), Some(9));
+    }
+
+    #[test]
+    fn it_computes_the_code_at_any_counter() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        hotp.set_count(7);
+        assert_eq!(hotp.get_at_counter(0).unwrap(), 755224);
+        assert_eq!(hotp.get_at_counter(9).unwrap(), 520489);
+        assert_eq!(hotp.count(), 7);
+    }
+
+    #[test]
+    fn it_checks_without_advancing_the_counter() {
+        let hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        assert!(hotp.check(969429, 5));
+        assert!(hotp.check(969429, 5));
+        assert!(!hotp.check(969429, 2));
+        assert_eq!(hotp.count(), 0);
+    }
+
+    #[test]
+    fn it_writes_a_provisioning_uri_with_the_current_counter() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        hotp.get().unwrap();
+        hotp.get().unwrap();
+        let uri = hotp.to_uri("ACME Co", "john.doe@email.com").unwrap();
+        assert_eq!(
+            uri,
+            "otpauth://hotp/ACME%20Co:john.doe%40email.com\
+             ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=ACME%20Co\
+             &algorithm=SHA1&digits=6&counter=2"
+        );
+        // Writing the URI does not advance the counter
+        assert_eq!(hotp.to_uri("ACME Co", "john.doe@email.com").unwrap(), uri);
+    }
+
+    #[test]
+    fn it_round_trips_a_provisioning_uri() {
+        let mut hotp = Hotp::new("12345678901234567890", 5, 8).unwrap();
+        let uri = hotp.to_uri("ACME Co", "john.doe@email.com").unwrap();
+        let mut parsed = Hotp::from_uri(&uri).unwrap();
+        assert_eq!(parsed.to_uri_with_metadata().unwrap(), uri);
+        assert_eq!(parsed.get().unwrap(), hotp.get().unwrap());
+    }
+
+    #[test]
+    fn it_requires_a_counter_in_the_uri() {
+        let result = Hotp::from_uri("otpauth://hotp/alice?secret=GEZDGNBV");
+        assert!(matches!(result, Err(HotpError::InvalidUri { .. })));
+    }
+
+    #[test]
+    fn it_builds_with_defaults() {
+        let mut hotp = HotpBuilder::new()
+            .secret("12345678901234567890")
+            .build()
+            .unwrap();
+        assert_eq!(hotp.get_string().unwrap(), "755224");
+    }
+
+    #[test]
+    fn it_builds_with_every_option() {
+        let mut hotp = HotpBuilder::new()
+            .secret("12345678901234567890")
+            .counter(9)
+            .digits(8)
+            .algorithm(Algorithm::Sha1)
+            .issuer("ACME Co")
+            .account("john.doe@email.com")
+            .build()
+            .unwrap();
+        assert_eq!(hotp.get().unwrap(), 45520489);
+        assert_eq!(hotp.issuer(), Some("ACME Co"));
+        assert_eq!(hotp.account(), Some("john.doe@email.com"));
+    }
+
+    #[test]
+    fn it_validates_the_built_options() {
+        let result = HotpBuilder::new()
+            .secret("12345678901234567890")
+            .digits(11)
+            .build();
+        assert!(matches!(
+            result,
+            Err(HotpError::InvalidDigits { digits: 11 })
+        ));
+        let result = HotpBuilder::new().build();
+        assert!(matches!(result, Err(HotpError::MissingSecret)));
+    }
+
+    #[test]
+    fn it_appends_a_checksum_digit() {
+        let mut hotp = HotpBuilder::new()
+            .secret("12345678901234567890")
+            .checksum(true)
+            .build()
+            .unwrap();
+        assert_eq!(hotp.get_string().unwrap(), "7552243");
+        assert_eq!(hotp.get_string().unwrap(), "2870822");
+        // A mistyped checksum is rejected along with the passcode.
+        assert_eq!(hotp.verify(3591520, 0), None);
+        assert_eq!(hotp.verify(3591526, 0), Some(2));
+    }
+
+    #[test]
+    fn it_refuses_to_write_a_uri_with_a_checksum() {
+        let hotp = HotpBuilder::new()
+            .secret("12345678901234567890")
+            .checksum(true)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            hotp.to_uri("ACME", "alice"),
+            Err(HotpError::UnsupportedUri { .. })
+        ));
+    }
+
+    #[test]
+    fn it_limits_the_digits_with_a_checksum() {
+        let result = HotpBuilder::new()
+            .secret("12345678901234567890")
+            .digits(9)
+            .checksum(true)
+            .build();
+        assert!(matches!(
+            result,
+            Err(HotpError::InvalidChecksumDigits { digits: 9 })
+        ));
+    }
+
+    #[test]
+    fn it_fails_once_the_counter_is_exhausted() {
+        let mut hotp = Hotp::new("12345678901234567890", u64::MAX - 1, 6).unwrap();
+        assert!(hotp.get().is_ok());
+        assert_eq!(hotp.count(), u64::MAX);
+        assert!(matches!(hotp.get(), Err(HotpError::CounterExhausted)));
+        assert_eq!(hotp.count(), u64::MAX);
+        assert!((&mut hotp).next().is_none());
+    }
+
+    #[test]
+    fn it_never_accepts_the_code_for_the_last_counter() {
+        let mut hotp = Hotp::new("12345678901234567890", u64::MAX, 6).unwrap();
+        let code = hotp.peek().unwrap();
+        assert_eq!(hotp.verify(code, 0), None);
+        hotp.set_count(u64::MAX - 1);
+        assert_eq!(hotp.verify(code, 1), None);
+    }
+
+    #[test]
+    fn it_reports_the_next_counter() {
+        let mut hotp = Hotp::new("12345678901234567890", 3, 6).unwrap();
+        assert_eq!(hotp.count(), 3);
+        assert_eq!(hotp.get().unwrap(), 969429);
+        assert_eq!(hotp.count(), 4);
+        hotp.verify(520489, 10);
+        assert_eq!(hotp.count(), 10);
+    }
+
+    #[test]
+    fn it_restores_the_counter() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        hotp.set_count(9);
+        assert_eq!(hotp.count(), 9);
+        assert_eq!(hotp.get().unwrap(), 520489);
+        hotp.set_count(1);
+        assert_eq!(hotp.get().unwrap(), 287082);
+    }
+
+    #[test]
+    fn it_peeks_without_advancing() {
+        let mut hotp = Hotp::new("12345678901234567890", 2, 6).unwrap();
+        assert_eq!(hotp.peek().unwrap(), 359152);
+        assert_eq!(hotp.peek().unwrap(), 359152);
+        assert_eq!(hotp.count(), 2);
+        assert_eq!(hotp.get().unwrap(), 359152);
+        assert_eq!(hotp.peek().unwrap(), 969429);
+    }
+
+    #[test]
+    fn it_resets_to_the_initial_counter() {
+        let mut hotp = Hotp::new("12345678901234567890", 4, 6).unwrap();
+        for _ in 0..3 {
+            hotp.get().unwrap();
+        }
+        assert_eq!(hotp.count(), 7);
+        hotp.reset();
+        assert_eq!(hotp.count(), 4);
+        assert_eq!(hotp.get().unwrap(), 338314);
+    }
+
+    // These are the HOTP values of RFC 4226 appendix D
+    const RFC_SEQUENCE: [u32; 10] = [
+        755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+    ];
+
+    #[test]
+    fn it_iterates_over_codes() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        let codes: Vec<u32> = (&mut hotp).take(10).map(Result::unwrap).collect();
+        assert_eq!(codes, RFC_SEQUENCE);
+        assert_eq!(hotp.count(), 10);
+    }
+
+    #[test]
+    fn it_generates_a_batch() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        let batch = hotp.generate_batch(10).unwrap();
+        let expected: Vec<String> = RFC_SEQUENCE
+            .iter()
+            .map(|code| format!("{code:06}"))
+            .collect();
+        assert_eq!(batch, expected);
+        assert_eq!(hotp.count(), 10);
+    }
+
+    #[test]
+    fn it_saves_and_restores_state_across_generators() {
+        let key = "12345678901234567890";
+        let mut first = Hotp::new(key, 0, 6).unwrap();
+        first.generate_batch(3).unwrap();
+        let state = first.save_state();
+        assert_eq!(state, HotpState { count: 3 });
+
+        let mut second = Hotp::new(key, 0, 6).unwrap();
+        second.restore_state(state);
+        assert_eq!(second.get().unwrap(), 969429);
+    }
+
+    #[test]
+    fn it_clones_independent_counters() {
+        let mut hotp = Hotp::new("12345678901234567890", 0, 6).unwrap();
+        let mut clone = hotp.clone();
+        assert_eq!(hotp.get().unwrap(), 755224);
+        assert_eq!(clone.get().unwrap(), 755224);
+        assert_eq!(clone.get().unwrap(), 287082);
+        assert_eq!(hotp.count(), 1);
+    }
 }
@@ -3,74 +3,901 @@
 //! Provides implementations for both IETF RFCs:
 //! * [4226](https://datatracker.ietf.org/doc/html/rfc4226): simple incrementing counter (HOTP)
 //! * [6238](https://datatracker.ietf.org/doc/html/rfc6238): time-based counter (TOTP)
+//! * [6287](https://datatracker.ietf.org/doc/html/rfc6287): challenge-response (OCRA)
 //!
 //! Custom HMAC-based one-time passcodes can be provided
 //! by types implementing the [`ToBytes`] trait.
+//!
+//! Any other digest, e.g. BLAKE2 or SM3, can be used for the HMAC with
+//! [`HmacOtp`].
+//!
+//! When the secret key cannot be handed to this crate (e.g. it lives on a
+//! hardware security module), the HMAC itself can be delegated to a
+//! [`MacFn`].
+//!
+//! With the `motp` feature, the older MD5-based Mobile-OTP scheme is
+//! also provided, for servers which support nothing else.
+//!
+//! # `no_std`
+//!
+//! The `std` feature is on by default. Without it the crate only needs
+//! `alloc`, and provides HOTP: [`Hotp`] and its builder, state and
+//! provisioning URIs, [`FixedHotp`], custom [`ToBytes`] generators and,
+//! with the `serde` feature, [`HotpConfig`]. TOTP, OCRA and Google
+//! Authenticator exports need a clock, so are only available with
+//! `std`, as are the features which build on them.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "async")]
+mod async_clock;
+mod base32;
+#[cfg(feature = "chrono")]
+mod chrono_clock;
+#[cfg(feature = "serde")]
+mod config;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fixed;
+mod hmac_otp;
 mod hotp;
+#[cfg(feature = "std")]
+mod migration;
+#[cfg(feature = "motp")]
+mod motp;
+#[cfg(feature = "std")]
+mod ocra;
+#[cfg(feature = "qr")]
+mod qr;
+#[cfg(feature = "rand")]
+mod recovery;
+#[cfg(feature = "rand")]
+mod secret;
+#[cfg(feature = "std")]
+mod store;
+#[cfg(feature = "std")]
+mod throttle;
+#[cfg(feature = "time")]
+mod time_clock;
+#[cfg(feature = "std")]
 mod totp;
-pub use hotp::Hotp;
-pub use totp::Totp;
+mod uri;
+#[cfg(feature = "wasm")]
+mod wasm;
+pub use base32::{format_base32, parse_base32};
+#[cfg(feature = "serde")]
+pub use config::HotpConfig;
+#[cfg(all(feature = "serde", feature = "std"))]
+pub use config::TotpConfig;
+pub use fixed::{FixedHotp, FixedOtp, Hotp6, Hotp8};
+#[cfg(feature = "std")]
+pub use fixed::{FixedTotp, Totp6, Totp8};
+pub use hmac_otp::HmacOtp;
+pub use hotp::{Hotp, HotpBuilder, HotpState};
+#[cfg(feature = "std")]
+pub use migration::{import_migration, MigratedAccount};
+#[cfg(feature = "motp")]
+pub use motp::Motp;
+#[cfg(feature = "std")]
+pub use ocra::{Ocra, OcraInput};
+#[cfg(feature = "rand")]
+pub use recovery::RecoveryCodes;
+#[cfg(feature = "std")]
+pub use store::{AccountKey, OtpStore};
+#[cfg(feature = "std")]
+pub use throttle::{RateLimiter, Verdict};
+#[cfg(feature = "std")]
+pub use totp::{NowFn, Totp, TotpBuilder};
 
-use hmac::{digest::InvalidLength, Mac};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+use hmac::{
+    digest::{core_api::BlockSizeUser, InvalidLength},
+    Hmac, Mac, SimpleHmac,
+};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroizing;
 
 #[derive(Debug, thiserror::Error)]
 pub enum HotpError {
     #[error("error when computing HMAC")]
-    InvalidLength(#[from] InvalidLength),
-    #[error("error when getting bytes for HMAC input: {err}")]
-    InputBytes { err: String },
+    InvalidLength(#[cfg_attr(feature = "std", from)] InvalidLength),
+    #[error("error when getting bytes for HMAC input")]
+    InputBytes {
+        #[source]
+        err: Box<dyn core::error::Error + Send + Sync>,
+    },
+    #[error("invalid Base32 secret: {err}")]
+    InvalidBase32 { err: String },
+    #[error("invalid character {0:?} in Base32 secret")]
+    InvalidBase32Character(char),
+    #[error("invalid hex secret: {0}")]
+    InvalidHex(#[cfg_attr(feature = "std", from)] hex::FromHexError),
+    #[error("passcodes must have between 1 and {MAX_DIGITS} digits, not {digits}")]
+    InvalidDigits { digits: u32 },
+    #[error("passcodes with a checksum digit must have between 1 and {MAX_CHECKSUM_DIGITS} digits, not {digits}")]
+    InvalidChecksumDigits { digits: u32 },
+    #[error("passcode alphabets need at least 2 symbols, not {symbols}")]
+    InvalidAlphabet { symbols: usize },
+    #[error("buffer of {len} bytes is too small for a passcode of {needed} bytes")]
+    BufferTooSmall { needed: usize, len: usize },
+    #[error("key of {length} bytes is shorter than the minimum of {MIN_KEY_LENGTH} bytes")]
+    WeakKey { length: usize },
+    #[error("the TOTP step must be a whole number of seconds, and at least one")]
+    InvalidStep,
+    #[error("there is no TOTP window before the first")]
+    NoPreviousWindow,
+    #[error("the secret is not available when the HMAC is computed externally")]
+    SecretUnavailable,
+    #[error("the HMAC digest has no standard name to export it with")]
+    NonStandardDigest,
+    #[error("invalid provisioning URI: {err}")]
+    InvalidUri { err: String },
+    #[error("the generator cannot be written as a provisioning URI: {reason}")]
+    UnsupportedUri { reason: &'static str },
+    #[error("the generator cannot be described by a config: {reason}")]
+    UnsupportedConfig { reason: &'static str },
+    #[error("HMAC of {len} bytes is too short to truncate to a passcode")]
+    Truncation { len: usize },
+    #[error("the HOTP counter cannot advance past its maximum")]
+    CounterExhausted,
+    #[error("no secret was given")]
+    MissingSecret,
+    #[error("invalid Google Authenticator export: {err}")]
+    InvalidMigration { err: String },
+    #[error("invalid OCRA suite or input: {err}")]
+    InvalidOcra { err: String },
+    #[cfg(feature = "qr")]
+    #[error("failed to render QR code: {err}")]
+    Qr { err: String },
+    #[cfg(feature = "rand")]
+    #[error("failed to generate a random secret: {err}")]
+    Random { err: String },
+}
+
+// Without `std` the dependencies' errors don't implement `Error`, so
+// can't be sources.
+#[cfg(not(feature = "std"))]
+impl From<InvalidLength> for HotpError {
+    fn from(err: InvalidLength) -> Self {
+        HotpError::InvalidLength(err)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<hex::FromHexError> for HotpError {
+    fn from(err: hex::FromHexError) -> Self {
+        HotpError::InvalidHex(err)
+    }
+}
+
+pub type OtpResult<T> = core::result::Result<T, HotpError>;
+
+/// The minimum key length, in bytes, accepted by the strict constructors.
+///
+/// RFC 4226 requires secrets of at least 128 bits, and recommends 160.
+pub const MIN_KEY_LENGTH: usize = 16;
+
+/// The key length, in bytes, recommended by RFC 4226.
+pub const DEFAULT_SECRET_LENGTH: usize = 20;
+
+fn check_key_length(key: &[u8]) -> OtpResult<()> {
+    if key.len() < MIN_KEY_LENGTH {
+        return Err(HotpError::WeakKey { length: key.len() });
+    }
+    Ok(())
+}
+
+/// The maximum number of digits in a passcode.
+///
+/// The HMAC is truncated to a 31-bit value, so longer passcodes would
+/// only ever be padded with leading zeroes.
+pub const MAX_DIGITS: u32 = 10;
+
+fn check_digits(digits: u32) -> OtpResult<()> {
+    if !(1..=MAX_DIGITS).contains(&digits) {
+        return Err(HotpError::InvalidDigits { digits });
+    }
+    Ok(())
+}
+
+/// The maximum number of digits in a passcode with a checksum digit,
+/// not counting the checksum, so that the passcode still fits in a `u32`.
+pub const MAX_CHECKSUM_DIGITS: u32 = 8;
+
+/// The checksum digit of RFC 4226 appendix C for the `digits` least
+/// significant digits of `code`: the Luhn algorithm, doubling every
+/// other digit starting from the rightmost.
+fn checksum_digit(mut code: u64, digits: u32) -> u32 {
+    const DOUBLED: [u64; 10] = [0, 2, 4, 6, 8, 1, 3, 5, 7, 9];
+    let mut total = 0;
+    for i in 0..digits {
+        let digit = code % 10;
+        code /= 10;
+        total += if i % 2 == 0 {
+            DOUBLED[digit as usize]
+        } else {
+            digit
+        };
+    }
+    ((10 - total % 10) % 10) as u32
+}
+
+impl HotpError {
+    /// Wrap the error from a custom [`ToBytes`] generator, keeping it as
+    /// the [source](core::error::Error::source) of the returned error.
+    pub fn input_bytes(err: impl Into<Box<dyn core::error::Error + Send + Sync>>) -> Self {
+        HotpError::InputBytes { err: err.into() }
+    }
+}
+
+/// Compare two byte strings in constant time, e.g. a passcode from
+/// [`Otp::get_string`] with one a user submitted.
+///
+/// Every byte is compared, rather than stopping at the first that
+/// differs, so the time taken does not reveal how much of `b` matched
+/// `a`. Inputs of different lengths are unequal, but are still compared
+/// up to the longer length, so the time depends only on that.
+///
+/// ```
+/// assert!(otp2::constant_time_eq(b"287082", b"287082"));
+/// assert!(!otp2::constant_time_eq(b"287082", b"287083"));
+/// assert!(!otp2::constant_time_eq(b"287082", b"28708"));
+/// ```
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut equal = (a.len() as u64).ct_eq(&(b.len() as u64));
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).copied().unwrap_or_default();
+        let y = b.get(i).copied().unwrap_or_default();
+        equal &= x.ct_eq(&y);
+    }
+    equal.into()
+}
+
+/// The first of the `candidates` whose passcode is `code`, e.g. the
+/// steps of a TOTP window.
+///
+/// Every candidate is computed and compared, rather than stopping at the
+/// first match, so the time taken does not reveal which one matched, or
+/// whether any did. A candidate whose passcode can't be computed never
+/// matches.
+fn find_match(
+    code: u32,
+    candidates: impl IntoIterator<Item = (u64, OtpResult<u32>)>,
+) -> Option<u64> {
+    let mut found = Choice::from(0);
+    let mut matched = 0;
+    for (candidate, expected) in candidates {
+        let equal = expected.map_or(Choice::from(0), |expected| expected.ct_eq(&code));
+        matched = u64::conditional_select(&matched, &candidate, equal & !found);
+        found |= equal;
+    }
+    bool::from(found).then_some(matched)
 }
 
-pub type OtpResult<T> = std::result::Result<T, HotpError>;
+/// Compare two passcodes in constant time, so the time taken does not
+/// reveal how close a submitted code was to the expected one.
+#[cfg(feature = "std")]
+fn codes_equal(a: u32, b: u32) -> bool {
+    constant_time_eq(&a.to_be_bytes(), &b.to_be_bytes())
+}
 
 /// Trait used to provide bytes as input to the HMAC algorithm.
+///
+/// A plain `u64` is a fixed counter, which is enough to experiment with
+/// a custom generator:
+///
+/// ```
+/// use otp2::{Algorithm, Otp};
+///
+/// let mut otp = Otp::from_generator(b"12345678901234567890", 1u64, 6, Algorithm::Sha1)?;
+/// assert_eq!(otp.get()?, 287082);
+/// // The counter is not advanced.
+/// assert_eq!(otp.get()?, 287082);
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+///
+/// HOTP and TOTP use the 8 big-endian bytes of a counter, but the HMAC
+/// input can be of any length, e.g. for a challenge-response scheme:
+///
+/// ```
+/// use otp2::{Algorithm, ClosureBytes, Otp};
+///
+/// let challenge = ClosureBytes(|| Ok(b"challenge: 12345678".to_vec()));
+/// let mut otp = Otp::from_generator(b"12345678901234567890", challenge, 6, Algorithm::Sha1)?;
+/// assert_eq!(otp.get()?, 151126);
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+///
+/// A generator which can fail, e.g. because it reads the counter from
+/// storage, should report its own error with [`HotpError::input_bytes`].
 pub trait ToBytes {
-    fn to_bytes(&mut self) -> OtpResult<[u8; 8]>;
+    /// The HMAC input, e.g. `[u8; 8]` for a counter or `Vec<u8>` for a
+    /// message of varying length.
+    type Bytes: AsRef<[u8]>;
+
+    fn to_bytes(&mut self) -> OtpResult<Self::Bytes>;
+}
+
+/// The counter itself, in big-endian order as RFC 4226 requires. Unlike
+/// [`Hotp`], it is never incremented.
+impl ToBytes for u64 {
+    type Bytes = [u8; 8];
+
+    fn to_bytes(&mut self) -> OtpResult<[u8; 8]> {
+        Ok(self.to_be_bytes())
+    }
+}
+
+/// A [`ToBytes`] implemented by calling a closure, to build a custom
+/// generator inline.
+///
+/// ```
+/// use otp2::{Algorithm, ClosureBytes, Otp};
+///
+/// // Count down rather than up.
+/// let mut counter = 2u64;
+/// let generator = ClosureBytes(move || {
+///     counter -= 1;
+///     Ok(counter.to_be_bytes())
+/// });
+/// let mut otp = Otp::from_generator(b"12345678901234567890", generator, 6, Algorithm::Sha1)?;
+/// assert_eq!(otp.get()?, 287082);
+/// assert_eq!(otp.get()?, 755224);
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+#[derive(Clone)]
+pub struct ClosureBytes<F>(pub F);
+
+impl<F, B> ToBytes for ClosureBytes<F>
+where
+    F: FnMut() -> OtpResult<B>,
+    B: AsRef<[u8]>,
+{
+    type Bytes = B;
+
+    fn to_bytes(&mut self) -> OtpResult<B> {
+        (self.0)()
+    }
+}
+
+impl<F> fmt::Debug for ClosureBytes<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ClosureBytes")
+    }
+}
+
+/// The hash algorithm used to compute the HMAC.
+///
+/// RFC 4226 only defines SHA-1, while RFC 6238 additionally allows
+/// SHA-256 and SHA-512.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    /// The name used for the algorithm in provisioning URIs.
+    fn uri_name(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+/// A caller-provided function computing the HMAC of the given
+/// counter bytes.
+///
+/// The returned digest is truncated in the same way as one computed
+/// by this crate. It must be `Send + Sync` so that generators can be
+/// shared between threads.
+pub type MacFn = Box<dyn Fn(&[u8]) -> OtpResult<Vec<u8>> + Send + Sync>;
+
+/// A [`MacFn`] shared between clones of a generator.
+type SharedMacFn = Arc<dyn Fn(&[u8]) -> OtpResult<Vec<u8>> + Send + Sync>;
+
+/// Where the HMAC of the counter bytes comes from.
+#[derive(Clone)]
+enum Key {
+    /// An HMAC keyed with the shared secret, computed in-process.
+    Secret {
+        /// Kept only to write provisioning URIs.
+        secret: Zeroizing<Vec<u8>>,
+        hmac: Box<KeyedHmac>,
+    },
+    /// A function which computes the HMAC without exposing the secret.
+    External(SharedMacFn),
+}
+
+impl Key {
+    /// Key an HMAC with the shared secret. The secret is overwritten with
+    /// zeroes when the generator is dropped.
+    fn secret(algorithm: Algorithm, key: Vec<u8>) -> Self {
+        let secret = Zeroizing::new(key);
+        let hmac = Box::new(KeyedHmac::new(algorithm, &secret));
+        Key::Secret { secret, hmac }
+    }
+
+    /// Key an HMAC over the digest `D` with the shared secret, as by
+    /// [`Self::secret`].
+    fn with_digest<D>(key: Vec<u8>) -> Self
+    where
+        D: Digest + BlockSizeUser + Clone + Send + Sync + 'static,
+    {
+        let secret = Zeroizing::new(key);
+        let mac: SimpleHmac<D> = new_hmac(&secret);
+        let hmac = Box::new(KeyedHmac::Other(Arc::new(move |c| finalize_hmac(&mac, c))));
+        Key::Secret { secret, hmac }
+    }
+
+    fn algorithm(&self) -> Option<Algorithm> {
+        match self {
+            Key::Secret { hmac, .. } => hmac.algorithm(),
+            Key::External(_) => None,
+        }
+    }
+
+    /// The algorithm to write in provisioning URIs and configs. Fails for
+    /// a digest other than those of [`Algorithm`], which other apps
+    /// wouldn't recognise.
+    fn standard_algorithm(&self) -> OtpResult<Algorithm> {
+        match self {
+            Key::Secret { hmac, .. } => hmac.algorithm().ok_or(HotpError::NonStandardDigest),
+            Key::External(_) => Ok(Algorithm::default()),
+        }
+    }
+
+    /// The shared secret, which is unknown for an external HMAC.
+    fn secret_bytes(&self) -> OtpResult<&[u8]> {
+        match self {
+            Key::Secret { secret, .. } => Ok(secret),
+            Key::External(_) => Err(HotpError::SecretUnavailable),
+        }
+    }
+}
+
+/// Never print the secret itself, so that generators can be safely
+/// logged.
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Secret { .. } => f.write_str("<redacted>"),
+            Key::External(_) => f.write_str("<external>"),
+        }
+    }
 }
 
 /// One-time passcodes.
 ///
+/// Passcodes are at most [`MAX_DIGITS`] digits long.
+///
 /// See [`Hotp`] and [`Totp`].
+#[derive(Clone)]
 pub struct Otp<G: ToBytes> {
-    key: String,
+    key: Key,
     generator: G,
     digits: u32,
+    /// Whether an RFC 4226 checksum digit follows the passcode.
+    checksum: bool,
+    encoding: Encoding,
+    issuer: Option<String>,
+    account: Option<String>,
+}
+
+impl<G: ToBytes + fmt::Debug> fmt::Debug for Otp<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Otp")
+            .field("key", &self.key)
+            .field("generator", &self.generator)
+            .field("digits", &self.digits)
+            .field("checksum", &self.checksum)
+            .field("algorithm", &self.key.algorithm())
+            .field("encoding", &self.encoding)
+            .field("issuer", &self.issuer)
+            .field("account", &self.account)
+            .finish()
+    }
 }
 
 impl<G: ToBytes> Otp<G> {
+    /// Get a generator which computes passcodes of `digits` from the
+    /// bytes of a custom `generator`, using an HMAC keyed with `key`.
+    ///
+    /// Fails if `digits` is not between 1 and [`MAX_DIGITS`].
+    pub fn from_generator(
+        key: impl Into<Vec<u8>>,
+        generator: G,
+        digits: u32,
+        algorithm: Algorithm,
+    ) -> OtpResult<Self> {
+        check_digits(digits)?;
+        Ok(Otp {
+            key: Key::secret(algorithm, key.into()),
+            generator,
+            digits,
+            checksum: false,
+            encoding: Encoding::decimal(),
+            issuer: None,
+            account: None,
+        })
+    }
+
     /// Generate a one-time passcode
+    ///
+    /// For generators which don't produce decimal passcodes, such as
+    /// [`Totp::new_steam`], the number is only meaningful once encoded
+    /// by [`Self::get_string`].
     pub fn get(&mut self) -> OtpResult<u32> {
         let c = self.generator.to_bytes()?;
-        let hs = hmac(self.key.clone(), &c)?;
-        let sbits = dt(&hs);
-        let snum = u32::from_be_bytes(sbits);
-        Ok(snum % 10_u32.pow(self.digits))
+        self.compute(c.as_ref())
+    }
+
+    /// Generate a one-time passcode, formatted with leading zeroes to
+    /// exactly the configured number of digits.
+    ///
+    /// This is the form that should be displayed to, and compared
+    /// against input from, the user.
+    pub fn get_string(&mut self) -> OtpResult<String> {
+        let code = self.get()?;
+        Ok(self.encoding.format(code, self.width()))
+    }
+
+    /// Generate a one-time passcode into `buf`, formatted as by
+    /// [`Self::get_string`] but without allocating.
+    ///
+    /// Returns the passcode as a view into the start of `buf`, or fails
+    /// if `buf` is too small to hold it, e.g. shorter than the configured
+    /// number of digits.
+    pub fn get_into<'a>(&mut self, buf: &'a mut [u8]) -> OtpResult<&'a str> {
+        let code = self.get()?;
+        self.encoding.write(code, self.width(), buf)
+    }
+
+    /// Encode passcodes using the symbols of `alphabet` rather than
+    /// decimal digits, e.g. to produce base-36 passcodes.
+    ///
+    /// The truncated HMAC value is written in base-N, where N is the
+    /// number of symbols, most significant symbol first. Fails if the
+    /// alphabet has fewer than two symbols.
+    pub fn with_alphabet(mut self, alphabet: &str) -> OtpResult<Self> {
+        let alphabet: Vec<char> = alphabet.chars().collect();
+        if alphabet.len() < 2 {
+            return Err(HotpError::InvalidAlphabet {
+                symbols: alphabet.len(),
+            });
+        }
+        self.encoding = Encoding {
+            alphabet,
+            reversed: false,
+        };
+        Ok(self)
+    }
+
+    /// Label the generator with the provider it is for, e.g. for display
+    /// in a list of accounts. This has no effect on the passcodes.
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Label the generator with the user's account name, e.g. their
+    /// email address. This has no effect on the passcodes.
+    pub fn with_account(mut self, account: impl Into<String>) -> Self {
+        self.account = Some(account.into());
+        self
+    }
+
+    /// The number of digits in a passcode, not counting any checksum
+    /// digit.
+    pub fn digits(&self) -> u32 {
+        self.digits
+    }
+
+    /// A short identifier for the secret, safe to log, e.g. to tell
+    /// generators apart or to correlate them with log entries.
+    ///
+    /// This is the first 8 bytes of the SHA-256 hash of the secret,
+    /// hex-encoded, which does not reveal the secret itself. Fails if
+    /// the HMAC is computed externally, since the secret is then unknown.
+    pub fn key_fingerprint(&self) -> OtpResult<String> {
+        let hash = Sha256::digest(self.key.secret_bytes()?);
+        Ok(hex::encode(&hash[..8]))
+    }
+
+    /// The provider the generator is for, if known.
+    pub fn issuer(&self) -> Option<&str> {
+        self.issuer.as_deref()
+    }
+
+    /// The user's account name, if known.
+    pub fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    /// Start a provisioning URI with the parameters shared by HOTP and
+    /// TOTP. Fails if the HMAC is computed externally, since the secret
+    /// is then unknown, or over a digest with no name in URIs, or if the
+    /// passcodes have a checksum digit or aren't decimal, which URIs
+    /// can't express.
+    fn uri_builder(
+        &self,
+        kind: &'static str,
+        issuer: &str,
+        account: &str,
+    ) -> OtpResult<uri::Builder> {
+        if self.checksum {
+            return Err(HotpError::UnsupportedUri {
+                reason: "there is no parameter for a checksum digit",
+            });
+        }
+        if self.encoding != Encoding::decimal() {
+            return Err(HotpError::UnsupportedUri {
+                reason: "passcodes must be decimal",
+            });
+        }
+        let secret = base32::encode(self.key.secret_bytes()?);
+        let algorithm = self.key.standard_algorithm()?;
+        let mut builder = uri::Builder::new(kind, issuer, account);
+        builder
+            .param("secret", &secret)
+            .param("issuer", issuer)
+            .param("algorithm", algorithm.uri_name())
+            .param("digits", &self.digits.to_string());
+        Ok(builder)
+    }
+
+    /// Compute the passcode for the given HMAC input, without touching
+    /// the generator.
+    fn compute(&self, c: &[u8]) -> OtpResult<u32> {
+        let snum = self.truncate(c)?;
+        // The modulus overflows a `u32` beyond 9 decimal digits. The
+        // truncated value is at most 31 bits, so the passcode always fits.
+        let modulus = self.encoding.modulus(self.digits);
+        let code = u64::from(snum) % modulus;
+        if self.checksum {
+            // At most `MAX_CHECKSUM_DIGITS` digits, so this fits.
+            Ok((code * 10) as u32 + checksum_digit(code, self.digits))
+        } else {
+            Ok(code as u32)
+        }
+    }
+
+    /// The 31-bit value truncated from the HMAC of the given input,
+    /// before it is reduced to a passcode.
+    fn truncate(&self, c: &[u8]) -> OtpResult<u32> {
+        let hs = match &self.key {
+            Key::Secret { hmac, .. } => hmac.compute(c),
+            Key::External(mac) => mac(c)?,
+        };
+        dynamic_truncation(&hs)
+    }
+
+    /// The number of symbols in a formatted passcode, including any
+    /// checksum digit.
+    fn width(&self) -> u32 {
+        self.digits + u32::from(self.checksum)
+    }
+
+    /// A submitted passcode of exactly [`Self::width`] decimal digits, so
+    /// that e.g. `"12345"` is not taken for `"012345"`.
+    fn parse_code(&self, input: &str) -> Option<u32> {
+        let well_formed =
+            input.len() == self.width() as usize && input.bytes().all(|b| b.is_ascii_digit());
+        well_formed.then(|| input.parse().ok()).flatten()
+    }
+
+    /// Write the zero-padded passcode for the given HMAC input, for use
+    /// by the [`Display`](fmt::Display) implementations. Formatting
+    /// fails if the passcode cannot be computed.
+    fn fmt_code(&self, c: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = self.compute(c).map_err(|_| fmt::Error)?;
+        f.write_str(&self.encoding.format(code, self.width()))
+    }
+}
+
+/// The symbols of a decimal passcode, as defined by RFC 4226.
+pub const DECIMAL_ALPHABET: &str = "0123456789";
+
+/// The symbols of a Steam Guard passcode.
+pub const STEAM_ALPHABET: &str = "23456789BCDFGHJKMNPQRTVWXY";
+
+/// How the truncated HMAC value is written out as a passcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Encoding {
+    alphabet: Vec<char>,
+    /// Steam Guard writes the least significant symbol first.
+    reversed: bool,
+}
+
+impl Encoding {
+    fn decimal() -> Self {
+        Encoding {
+            alphabet: DECIMAL_ALPHABET.chars().collect(),
+            reversed: false,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn steam() -> Self {
+        Encoding {
+            alphabet: STEAM_ALPHABET.chars().collect(),
+            reversed: true,
+        }
+    }
+
+    fn radix(&self) -> u64 {
+        self.alphabet.len() as u64
+    }
+
+    /// The number of distinct passcodes of `digits` symbols. When this
+    /// doesn't fit in a `u64`, neither does any truncated value exceed it.
+    fn modulus(&self, digits: u32) -> u64 {
+        self.radix().checked_pow(digits).unwrap_or(u64::MAX)
+    }
+
+    /// Write the passcode as UTF-8 into the start of `buf`, as
+    /// [`Self::format`] would format it.
+    fn write<'a>(&self, code: u32, digits: u32, buf: &'a mut [u8]) -> OtpResult<&'a str> {
+        let symbols = || {
+            let mut value = u64::from(code);
+            (0..digits).map(move |_| {
+                let symbol = self.alphabet[(value % self.radix()) as usize];
+                value /= self.radix();
+                symbol
+            })
+        };
+        let needed = symbols().map(char::len_utf8).sum();
+        if buf.len() < needed {
+            return Err(HotpError::BufferTooSmall {
+                needed,
+                len: buf.len(),
+            });
+        }
+        // Symbols are produced least significant first, so are written
+        // from the end unless the encoding is reversed.
+        let mut start = if self.reversed { 0 } else { needed };
+        for symbol in symbols() {
+            let len = symbol.len_utf8();
+            if !self.reversed {
+                start -= len;
+            }
+            symbol.encode_utf8(&mut buf[start..start + len]);
+            if self.reversed {
+                start += len;
+            }
+        }
+        Ok(core::str::from_utf8(&buf[..needed]).expect("symbols are valid UTF-8"))
+    }
+
+    fn format(&self, code: u32, digits: u32) -> String {
+        let encoded = encode(code.into(), &self.alphabet, digits as usize);
+        if self.reversed {
+            encoded.chars().rev().collect()
+        } else {
+            encoded
+        }
+    }
+}
+
+/// Encode `value` in base-N over `alphabet`, most significant symbol
+/// first, padded to `length` symbols with the alphabet's first symbol.
+fn encode(mut value: u64, alphabet: &[char], length: usize) -> String {
+    let radix = alphabet.len() as u64;
+    let mut symbols = vec![alphabet[0]; length];
+    for symbol in symbols.iter_mut().rev() {
+        *symbol = alphabet[(value % radix) as usize];
+        value /= radix;
+    }
+    symbols.into_iter().collect()
+}
+
+/// An HMAC which has already been keyed, so that the key schedule
+/// runs once per generator rather than once per passcode.
+///
+/// Note that the `hmac` crate does not support zeroizing the keyed state.
+#[derive(Clone)]
+enum KeyedHmac {
+    Sha1(Hmac<Sha1>),
+    Sha256(Hmac<Sha256>),
+    Sha512(Hmac<Sha512>),
+    /// An HMAC over any other digest, from [`HmacOtp`].
+    Other(KeyedDigestHmac),
+}
+
+/// A keyed HMAC over a digest chosen by the caller, hiding the digest's
+/// type.
+type KeyedDigestHmac = Arc<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
+
+impl KeyedHmac {
+    fn new(algorithm: Algorithm, key: &[u8]) -> Self {
+        match algorithm {
+            Algorithm::Sha1 => KeyedHmac::Sha1(new_hmac(key)),
+            Algorithm::Sha256 => KeyedHmac::Sha256(new_hmac(key)),
+            Algorithm::Sha512 => KeyedHmac::Sha512(new_hmac(key)),
+        }
+    }
+
+    fn algorithm(&self) -> Option<Algorithm> {
+        match self {
+            KeyedHmac::Sha1(_) => Some(Algorithm::Sha1),
+            KeyedHmac::Sha256(_) => Some(Algorithm::Sha256),
+            KeyedHmac::Sha512(_) => Some(Algorithm::Sha512),
+            KeyedHmac::Other(_) => None,
+        }
+    }
+
+    fn compute(&self, counter: &[u8]) -> Vec<u8> {
+        match self {
+            KeyedHmac::Sha1(mac) => finalize_hmac(mac, counter),
+            KeyedHmac::Sha256(mac) => finalize_hmac(mac, counter),
+            KeyedHmac::Sha512(mac) => finalize_hmac(mac, counter),
+            KeyedHmac::Other(mac) => mac(counter),
+        }
     }
 }
 
-type Sha1Hmac = hmac::Hmac<sha1::Sha1>;
+fn new_hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8]) -> M {
+    <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
 
-fn hmac(key: String, counter: &[u8]) -> OtpResult<[u8; 20]> {
-    let mut mac = Sha1Hmac::new_from_slice(key.as_bytes())?;
+fn finalize_hmac<M: Mac + Clone>(mac: &M, counter: &[u8]) -> Vec<u8> {
+    let mut mac = mac.clone();
     mac.update(counter);
-    Ok(mac.finalize().into_bytes().into())
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The dynamic truncation of RFC 4226 section 5.3: the 31-bit value at
+/// the offset given by the low 4 bits of the digest's last byte.
+///
+/// This works for digests of any length, e.g. from SHA-256 or SHA-512,
+/// as a building block for variants of HOTP. Fails if the digest is too
+/// short for the offset it encodes, which it can't be if it has at least
+/// 19 bytes.
+///
+/// ```
+/// let digest = hex_literal::hex!("1f8698690e02ca16618550ef7f19da8e945b555a");
+/// assert_eq!(otp2::dynamic_truncation(&digest)?, 0x50ef7f19);
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+pub fn dynamic_truncation(digest: &[u8]) -> OtpResult<u32> {
+    dt(digest).map(u32::from_be_bytes)
 }
 
-fn dt(hs: &[u8; 20]) -> [u8; 4] {
-    let offset = dt_offset(hs);
-    let mut substr = dt_substr(hs, offset);
+fn dt(hs: &[u8]) -> OtpResult<[u8; 4]> {
+    let offset = dt_offset(hs)?;
+    let mut substr = dt_substr(hs, offset)?;
     substr[0] &= 0b0111_1111;
-    substr
+    Ok(substr)
 }
 
-fn dt_substr(hs: &[u8; 20], offset: u8) -> [u8; 4] {
-    let substr = &hs[offset as usize..(offset + 4) as usize];
-    substr.try_into().unwrap()
+fn dt_substr(hs: &[u8], offset: u8) -> OtpResult<[u8; 4]> {
+    let offset = usize::from(offset);
+    hs.get(offset..offset + 4)
+        .and_then(|substr| substr.try_into().ok())
+        .ok_or(HotpError::Truncation { len: hs.len() })
 }
 
-fn dt_offset(hs: &[u8; 20]) -> u8 {
-    hs[19] & 0b1111
+fn dt_offset(hs: &[u8]) -> OtpResult<u8> {
+    hs.last()
+        .map(|last| last & 0b1111)
+        .ok_or(HotpError::Truncation { len: 0 })
 }
 
 #[cfg(test)]
@@ -78,14 +905,18 @@ mod test {
     use hex::FromHex;
     use test_case::test_case;
 
-    use crate::{dt, dt_offset, dt_substr, hmac};
+    use crate::{
+        checksum_digit, constant_time_eq, dt, dt_offset, dt_substr, dynamic_truncation, encode,
+        find_match, Algorithm, ClosureBytes, Encoding, HotpError, KeyedHmac, Otp, OtpResult,
+        ToBytes,
+    };
 
     #[test]
     fn it_computes_correct_offset() {
         let s: [u8; 20] = hex_literal::hex!("1f8698690e02ca16618550ef7f19da8e945b555a");
         // Last byte is 0x5a, with low 4 bits 0xa
         let expected: [u8; 1] = hex_literal::hex!("0a");
-        let actual = dt_offset(&s);
+        let actual = dt_offset(&s).unwrap();
         assert_eq!(actual, expected[0]);
     }
 
@@ -93,7 +924,7 @@ mod test {
     fn it_computes_correct_4byte_substring() {
         let s: [u8; 20] = hex_literal::hex!("1f8698690e02ca16618550ef7f19da8e945b555a");
         let expected: [u8; 4] = hex_literal::hex!("50ef7f19");
-        let actual = dt_substr(&s, dt_offset(&s));
+        let actual = dt_substr(&s, dt_offset(&s).unwrap()).unwrap();
         assert_eq!(actual, expected);
     }
 
@@ -101,10 +932,36 @@ mod test {
     fn it_computes_correct_dt() {
         let s: [u8; 20] = hex_literal::hex!("1f8698690e02ca166185ffef7f19da8e945b555a");
         let expected: [u8; 4] = hex_literal::hex!("7fef7f19");
-        let actual = dt(&s);
+        let actual = dt(&s).unwrap();
         assert_eq!(actual, expected);
     }
 
+    #[test_case(&hex_literal::hex!("1f8698690e02ca166185ffef7f19da8e945b555a"), 0x7fef7f19 ; "sha1")]
+    #[test_case(
+        &hex_literal::hex!("1f8698690e02ca16618550ef7f19da8e945b555a1f8698690e02ca1661855003"),
+        0x690e02ca ;
+        "sha256"
+    )]
+    fn it_truncates_digests_of_any_length(digest: &[u8], expected: u32) {
+        assert_eq!(dynamic_truncation(digest).unwrap(), expected);
+    }
+
+    // The offset from the last byte, 0xf, needs a digest of 19 bytes.
+    #[test_case(&[] ; "empty")]
+    #[test_case(&hex_literal::hex!("1f8698690e02ca16618550ef7f19da8e0f") ; "too short for the offset")]
+    fn it_rejects_short_digests(hs: &[u8]) {
+        assert!(matches!(dt(hs), Err(HotpError::Truncation { len }) if len == hs.len()));
+    }
+
+    #[test]
+    fn it_computes_correct_offset_for_longer_digests() {
+        let s: [u8; 32] =
+            hex_literal::hex!("1f8698690e02ca16618550ef7f19da8e945b555a1f8698690e02ca1661855003");
+        let expected: [u8; 4] = hex_literal::hex!("690e02ca");
+        assert_eq!(dt_offset(&s).unwrap(), 3);
+        assert_eq!(dt_substr(&s, 3).unwrap(), expected);
+    }
+
     #[test_case(0, "cc93cf18508d94934c64b65d8ba7667fb7cde4b0")]
     #[test_case(1, "75a48a19d4cbe100644e8ac1397eea747a2d33ab")]
     #[test_case(2, "0bacb7fa082fef30782211938bc1c5e70416ff44")]
@@ -117,8 +974,114 @@ mod test {
     #[test_case(9, "1637409809a679dc698207310c8c7fc07290d9e5")]
     fn it_computes_correct_hmac(counter: u64, expected: &str) {
         let expected = <[u8; 20]>::from_hex(expected).unwrap();
-        let key = "12345678901234567890".to_string();
-        let hmac = hmac(key, &counter.to_be_bytes()).unwrap();
+        let key = b"12345678901234567890";
+        let hmac = KeyedHmac::new(Algorithm::Sha1, key).compute(&counter.to_be_bytes());
         assert_eq!(hmac, expected);
     }
+
+    #[test_case(755224, 6, "755224")]
+    #[test_case(1, 6, "000001")]
+    #[test_case(7081804, 8, "07081804")]
+    fn it_pads_codes_with_leading_zeroes(code: u32, digits: u32, expected: &str) {
+        assert_eq!(Encoding::decimal().format(code, digits), expected);
+    }
+
+    #[test_case(0, "01", 4, "0000")]
+    #[test_case(5, "01", 4, "0101")]
+    #[test_case(35, "0123456789abcdefghijklmnopqrstuvwxyz", 3, "00z")]
+    #[test_case(1234, "0123456789", 3, "234"; "truncates to length")]
+    fn it_encodes_over_an_alphabet(value: u64, alphabet: &str, length: usize, expected: &str) {
+        let alphabet: Vec<char> = alphabet.chars().collect();
+        assert_eq!(encode(value, &alphabet, length), expected);
+    }
+
+    #[test_case("012345", 6 => Some(12345))]
+    #[test_case("12345", 6 => None ; "missing leading zero")]
+    #[test_case("0123456", 6 => None ; "too long")]
+    #[test_case("+12345", 6 => None ; "sign")]
+    #[test_case("12 345", 6 => None ; "space")]
+    #[test_case("0147483647", 10 => Some(147483647) ; "ten digits")]
+    #[test_case("9999999999", 10 => None ; "beyond a u32")]
+    fn it_parses_typed_codes(input: &str, digits: u32) -> Option<u32> {
+        let otp =
+            Otp::from_generator("12345678901234567890", 0u64, digits, Algorithm::Sha1).unwrap();
+        otp.parse_code(input)
+    }
+
+    #[cfg(feature = "std")]
+    #[test_case(94287082, 94287082 => true)]
+    #[test_case(94287082, 94287083 => false)]
+    #[test_case(0, 1 << 24 => false)]
+    fn it_compares_codes_in_constant_time(a: u32, b: u32) -> bool {
+        crate::codes_equal(a, b)
+    }
+
+    #[test_case(5 => Some(1) ; "first of two matches")]
+    #[test_case(1 => Some(0) ; "first candidate")]
+    #[test_case(9 => None ; "no match")]
+    fn it_finds_the_first_match_without_stopping(code: u32) -> Option<u64> {
+        let candidates = [
+            (0, Ok(1)),
+            (1, Ok(5)),
+            (2, Ok(5)),
+            (3, Err(HotpError::CounterExhausted)),
+        ];
+        find_match(code, candidates)
+    }
+
+    #[test_case(b"287082", b"287082" => true ; "equal")]
+    #[test_case(b"287082", b"287083" => false ; "unequal of the same length")]
+    #[test_case(b"287082", b"28708" => false ; "shorter")]
+    #[test_case(b"28708", b"287082" => false ; "longer")]
+    #[test_case(b"", b"" => true ; "empty")]
+    #[test_case(b"\0", b"" => false ; "padding is not equality")]
+    fn it_compares_byte_strings_in_constant_time(a: &[u8], b: &[u8]) -> bool {
+        constant_time_eq(a, b)
+    }
+
+    // 7992739871 is the usual example for the Luhn algorithm.
+    #[test_case(755224, 6 => 3)]
+    #[test_case(287082, 6 => 2)]
+    #[test_case(1, 6 => 8; "leading zeroes")]
+    #[test_case(7992739871, 10 => 3)]
+    fn it_computes_checksum_digits(code: u64, digits: u32) -> u32 {
+        checksum_digit(code, digits)
+    }
+
+    // The RFC 4226 vectors, with the counter given as a `Vec`, and a
+    // longer message computed with Python's `hmac` module.
+    #[test_case(1u64.to_be_bytes().to_vec() => 287082 ; "counter")]
+    #[test_case(9u64.to_be_bytes().to_vec() => 520489 ; "another counter")]
+    #[test_case(b"challenge: 12345678".to_vec() => 151126 ; "longer message")]
+    fn it_accepts_inputs_of_any_length(input: Vec<u8>) -> u32 {
+        let generator = ClosureBytes(move || Ok(input.clone()));
+        let mut otp =
+            Otp::from_generator(b"12345678901234567890", generator, 6, Algorithm::Sha1).unwrap();
+        otp.get().unwrap()
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("counter storage is unavailable")]
+    struct StorageError;
+
+    struct FailingBytes;
+
+    impl ToBytes for FailingBytes {
+        type Bytes = [u8; 8];
+
+        fn to_bytes(&mut self) -> OtpResult<[u8; 8]> {
+            Err(HotpError::input_bytes(StorageError))
+        }
+    }
+
+    #[test]
+    fn it_keeps_the_source_of_input_errors() {
+        let mut otp =
+            Otp::from_generator(b"12345678901234567890", FailingBytes, 6, Algorithm::Sha1).unwrap();
+        let err = otp.get().unwrap_err();
+        assert!(matches!(err, HotpError::InputBytes { .. }));
+        let source = core::error::Error::source(&err).unwrap();
+        assert!(source.is::<StorageError>());
+        assert_eq!(source.to_string(), "counter storage is unavailable");
+    }
 }
@@ -0,0 +1,290 @@
+//! Google Authenticator's `otpauth-migration://` export, which packs
+//! several accounts into one QR code as a Base64-encoded protobuf
+//! `MigrationPayload`.
+//!
+//! See <https://github.com/google/google-authenticator-android/issues/118>
+//! for the reverse-engineered schema.
+
+use unix_time::Instant;
+
+use crate::{totp::SYSTEM_NOW, uri, Algorithm, Hotp, HotpError, Otp, OtpResult, ToBytes, Totp};
+
+const PREFIX: &str = "otpauth-migration://offline?";
+
+/// A generator imported by [`import_migration`].
+#[derive(Debug)]
+pub enum MigratedAccount {
+    Hotp(Hotp),
+    Totp(Totp),
+}
+
+/// Import every account in a Google Authenticator
+/// `otpauth-migration://offline?data=...` export.
+///
+/// Each generator carries the issuer and account name from the export.
+/// TOTP generators use the 30 second steps from the Unix epoch that
+/// Google Authenticator assumes. Fails if the URI or its payload is
+/// malformed, or if an account uses an unsupported algorithm such as
+/// MD5.
+pub fn import_migration(uri: &str) -> OtpResult<Vec<MigratedAccount>> {
+    let query = uri
+        .get(..PREFIX.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(PREFIX))
+        .map(|_| &uri[PREFIX.len()..])
+        .ok_or_else(|| invalid("expected an otpauth-migration://offline URI"))?;
+    let data = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("data="))
+        .ok_or_else(|| invalid("missing data"))?;
+    let payload = base64_decode(&uri::decode(data)?)?;
+
+    let mut accounts = Vec::new();
+    let mut reader = Reader::new(&payload);
+    while let Some((field, value)) = reader.field()? {
+        // Field 1 is the repeated `OtpParameters`; the others describe
+        // the batch and are of no use here.
+        if let (1, Value::Bytes(parameters)) = (field, value) {
+            accounts.push(import_account(parameters)?);
+        }
+    }
+    Ok(accounts)
+}
+
+fn import_account(parameters: &[u8]) -> OtpResult<MigratedAccount> {
+    let mut secret = Vec::new();
+    let mut name = String::new();
+    let mut issuer = String::new();
+    let mut algorithm = Algorithm::Sha1;
+    let mut digits = 6;
+    let mut totp = true;
+    let mut counter = 0;
+
+    let mut reader = Reader::new(parameters);
+    while let Some((field, value)) = reader.field()? {
+        match (field, value) {
+            (1, Value::Bytes(bytes)) => secret = bytes.to_vec(),
+            (2, Value::Bytes(bytes)) => name = string(bytes)?,
+            (3, Value::Bytes(bytes)) => issuer = string(bytes)?,
+            (4, Value::Varint(value)) => {
+                algorithm = match value {
+                    0 | 1 => Algorithm::Sha1,
+                    2 => Algorithm::Sha256,
+                    3 => Algorithm::Sha512,
+                    _ => return Err(invalid(format!("unsupported algorithm {value}"))),
+                }
+            }
+            (5, Value::Varint(value)) => {
+                digits = match value {
+                    0 | 1 => 6,
+                    2 => 8,
+                    _ => return Err(invalid(format!("unsupported digit count {value}"))),
+                }
+            }
+            (6, Value::Varint(value)) => {
+                totp = match value {
+                    0 | 2 => true,
+                    1 => false,
+                    _ => return Err(invalid(format!("unsupported OTP type {value}"))),
+                }
+            }
+            (7, Value::Varint(value)) => counter = value,
+            _ => {}
+        }
+    }
+
+    // The name is usually labelled with the issuer as in a provisioning
+    // URI, e.g. "ACME Co:alice@example.com".
+    let (issuer, account) = match name.split_once(':') {
+        Some((prefix, account)) if issuer.is_empty() || prefix == issuer => {
+            (prefix.to_string(), account.trim_start().to_string())
+        }
+        _ => (issuer, name),
+    };
+
+    let account = if totp {
+        let totp =
+            Totp::new_with_algorithm(secret, Instant::at(0, 0), 30, digits, algorithm, SYSTEM_NOW)?;
+        MigratedAccount::Totp(label(totp, issuer, account))
+    } else {
+        let hotp = Hotp::new_with_algorithm(secret, counter, digits, algorithm)?;
+        MigratedAccount::Hotp(label(hotp, issuer, account))
+    };
+    Ok(account)
+}
+
+fn label<G: ToBytes>(otp: Otp<G>, issuer: String, account: String) -> Otp<G> {
+    let otp = otp.with_account(account);
+    if issuer.is_empty() {
+        otp
+    } else {
+        otp.with_issuer(issuer)
+    }
+}
+
+fn string(bytes: &[u8]) -> OtpResult<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| invalid("label is not valid UTF-8"))
+}
+
+fn invalid(err: impl Into<String>) -> HotpError {
+    HotpError::InvalidMigration { err: err.into() }
+}
+
+/// A decoded protobuf field value.
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Reads the fields of a protobuf message in order.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    /// The next field number and value, or `None` at the end of the
+    /// message.
+    fn field(&mut self) -> OtpResult<Option<(u64, Value<'a>)>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0b111 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = usize::try_from(self.varint()?)
+                    .map_err(|_| invalid("field length is too large"))?;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            wire_type => return Err(invalid(format!("unsupported wire type {wire_type}"))),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+
+    fn varint(&mut self) -> OtpResult<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self
+                .bytes
+                .split_first()
+                .ok_or_else(|| invalid("truncated payload"))?;
+            self.bytes = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint is too long"))
+    }
+
+    fn take(&mut self, len: usize) -> OtpResult<&'a [u8]> {
+        if len > self.bytes.len() {
+            return Err(invalid("truncated payload"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+/// Decode standard, optionally padded, Base64.
+fn base64_decode(input: &str) -> OtpResult<Vec<u8>> {
+    let input = input.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in input.chars() {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            _ => return Err(invalid(format!("invalid character {c:?} in Base64 data"))),
+        };
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    if bits >= 6 {
+        return Err(invalid("truncated Base64 data"));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+    use unix_time::Instant;
+
+    use crate::{
+        migration::{base64_decode, import_migration, MigratedAccount},
+        HotpError,
+    };
+
+    // Two accounts: a SHA-1 TOTP labelled "ACME Co:john.doe@email.com",
+    // and an 8 digit SHA-256 HOTP named "alice" at counter 5, both with
+    // the RFC 4226 secret.
+    const EXPORT: &str = "otpauth-migration://offline?data=\
+        CkEKFDEyMzQ1Njc4OTAxMjM0NTY3ODkwEhpBQ01FIENvOmpvaG4uZG9lQGVtYWlsLmNvbRoHQUNNRSBDbyABKAEw\
+        AgolChQxMjM0NTY3ODkwMTIzNDU2Nzg5MBIFYWxpY2UgAigCMAE4BRABGAEgACjAxAc%3D";
+
+    #[test]
+    fn it_imports_every_account() {
+        let accounts = import_migration(EXPORT).unwrap();
+        assert_eq!(accounts.len(), 2);
+
+        let MigratedAccount::Totp(totp) = &accounts[0] else {
+            panic!("expected a TOTP account");
+        };
+        assert_eq!(totp.issuer(), Some("ACME Co"));
+        assert_eq!(totp.account(), Some("john.doe@email.com"));
+        assert_eq!(totp.get_at(Instant::at(59, 0)).unwrap(), 287082);
+
+        let MigratedAccount::Hotp(hotp) = &accounts[1] else {
+            panic!("expected a HOTP account");
+        };
+        assert_eq!(hotp.issuer(), None);
+        assert_eq!(hotp.account(), Some("alice"));
+        assert_eq!(
+            hotp.to_uri_with_metadata().unwrap(),
+            "otpauth://hotp/alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &algorithm=SHA256&digits=8&counter=5"
+        );
+    }
+
+    #[test_case("TWFu", b"Man")]
+    #[test_case("TWE=", b"Ma")]
+    #[test_case("TQ==", b"M")]
+    #[test_case("TQ", b"M" ; "unpadded")]
+    fn it_decodes_base64(input: &str, expected: &[u8]) {
+        assert_eq!(base64_decode(input).unwrap(), expected);
+    }
+
+    #[test_case("otpauth://totp/alice?secret=GEZDGNBV" ; "wrong scheme")]
+    #[test_case("otpauth-migration://offline?version=1" ; "missing data")]
+    #[test_case("otpauth-migration://offline?data=!!!!" ; "invalid base64")]
+    #[test_case("otpauth-migration://offline?data=CkE%3D" ; "truncated payload")]
+    // One account whose algorithm is MD5
+    #[test_case("otpauth-migration://offline?data=CgIgBA%3D%3D" ; "unsupported algorithm")]
+    fn it_rejects_malformed_exports(uri: &str) {
+        let result = import_migration(uri);
+        assert!(matches!(result, Err(HotpError::InvalidMigration { .. })));
+    }
+}
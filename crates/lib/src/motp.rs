@@ -0,0 +1,165 @@
+//! Mobile-OTP (mOTP), a time-based scheme predating RFC 6238 which is
+//! still spoken by some VPN servers, such as OpenVPN and strongSwan
+//! plugins.
+//!
+//! A passcode is the first 6 hex digits of the MD5 hash of the number
+//! of 10 second windows since the Unix epoch, the secret and the PIN,
+//! all as text. MD5 is broken as a hash, so mOTP should only be used to
+//! talk to systems which support nothing better.
+
+use std::fmt;
+
+use md5::{Digest, Md5};
+use subtle::{Choice, ConstantTimeEq};
+use unix_time::Instant;
+use zeroize::Zeroizing;
+
+use crate::{totp::SYSTEM_NOW, HotpError, OtpResult};
+
+/// The length of an mOTP window, in seconds.
+const STEP: u64 = 10;
+
+/// The number of hex digits in an mOTP passcode.
+const LENGTH: usize = 6;
+
+/// Mobile-OTP generator.
+///
+/// The function `F` provides the "now" value, as for
+/// [`Totp`](crate::Totp).
+///
+/// ```
+/// use otp2::Motp;
+///
+/// let motp = Motp::new("1234567890abcdef", "1234")?;
+/// let code = motp.get();
+/// assert!(motp.verify(&code, 1));
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+pub struct Motp<F = fn() -> Instant> {
+    secret: Zeroizing<String>,
+    pin: Zeroizing<String>,
+    now: F,
+}
+
+impl Motp {
+    /// Get an mOTP generator from the `secret` shared with the server,
+    /// usually 16 hex digits, and the user's `pin`.
+    ///
+    /// Fails if `secret` is empty.
+    pub fn new(secret: &str, pin: &str) -> OtpResult<Self> {
+        Motp::new_with_now(secret, pin, SYSTEM_NOW)
+    }
+}
+
+impl<F: Fn() -> Instant> Motp<F> {
+    /// Get an mOTP generator which uses `now` to provide the current
+    /// time.
+    ///
+    /// See [`Motp::new`].
+    pub fn new_with_now(secret: &str, pin: &str, now: F) -> OtpResult<Self> {
+        if secret.is_empty() {
+            return Err(HotpError::MissingSecret);
+        }
+        Ok(Motp {
+            secret: Zeroizing::new(secret.into()),
+            pin: Zeroizing::new(pin.into()),
+            now,
+        })
+    }
+
+    /// The passcode for the current window.
+    pub fn get(&self) -> String {
+        self.get_at((self.now)())
+    }
+
+    /// The passcode for the window containing `at`.
+    pub fn get_at(&self, at: Instant) -> String {
+        self.code_at_step(at.secs() / STEP)
+    }
+
+    /// Check a submitted `code` against the passcodes for the current
+    /// window and the `skew` windows either side of it.
+    ///
+    /// mOTP servers traditionally allow for three minutes of drift, a
+    /// `skew` of 18. Hex digits may be upper or lower case. Every
+    /// window is compared, each in constant time.
+    pub fn verify(&self, code: &str, skew: u64) -> bool {
+        if code.len() != LENGTH {
+            return false;
+        }
+        let code = code.to_ascii_lowercase();
+        let current = (self.now)().secs() / STEP;
+        // Every window is compared, rather than stopping at the first
+        // match, so the time taken does not reveal which one matched.
+        let mut found = Choice::from(0);
+        for step in current.saturating_sub(skew)..=current.saturating_add(skew) {
+            found |= self.code_at_step(step).as_bytes().ct_eq(code.as_bytes());
+        }
+        found.into()
+    }
+
+    fn code_at_step(&self, step: u64) -> String {
+        let mut md5 = Md5::new();
+        md5.update(step.to_string());
+        md5.update(self.secret.as_bytes());
+        md5.update(self.pin.as_bytes());
+        let mut code = hex::encode(md5.finalize());
+        code.truncate(LENGTH);
+        code
+    }
+}
+
+impl<F> fmt::Debug for Motp<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Motp")
+            .field("secret", &"<redacted>")
+            .field("pin", &"<redacted>")
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+    use unix_time::Instant;
+
+    use crate::{HotpError, Motp};
+
+    // Computed from the reference definition,
+    // `md5(epoch / 10 + secret + pin)[0..6]`, with Python's `hashlib`.
+    #[test_case(1_111_111_110, "1234567890abcdef", "1234", "bf483b")]
+    #[test_case(1_234_567_890, "1234567890abcdef", "1234", "f52dc6")]
+    #[test_case(1_234_567_899, "1234567890abcdef", "1234", "f52dc6" ; "same window")]
+    #[test_case(1_234_567_900, "1234567890abcdef", "1234", "43364f" ; "next window")]
+    #[test_case(1_234_567_890, "e3152afee62599c8", "6789", "66cfb8")]
+    fn it_computes_mobile_otp(secs: u64, secret: &str, pin: &str, expected: &str) {
+        let motp = Motp::new(secret, pin).unwrap();
+        assert_eq!(motp.get_at(Instant::at(secs, 0)), expected);
+    }
+
+    #[test]
+    fn it_verifies_within_the_skew() {
+        let now = || Instant::at(1_234_567_900, 0);
+        let motp = Motp::new_with_now("1234567890abcdef", "1234", now).unwrap();
+        assert!(motp.verify("43364f", 0));
+        assert!(motp.verify("43364F", 0));
+        assert!(!motp.verify("f52dc6", 0));
+        assert!(motp.verify("f52dc6", 1));
+        assert!(!motp.verify("f52dc", 1));
+    }
+
+    #[test]
+    fn it_requires_a_secret() {
+        assert!(matches!(
+            Motp::new("", "1234"),
+            Err(HotpError::MissingSecret)
+        ));
+    }
+
+    #[test]
+    fn it_redacts_the_secret_and_pin() {
+        let motp = Motp::new("1234567890abcdef", "1234").unwrap();
+        let debug = format!("{motp:?}");
+        assert!(!debug.contains("1234"));
+    }
+}
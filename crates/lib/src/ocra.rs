@@ -0,0 +1,443 @@
+//! OCRA, the challenge-response passcodes of
+//! [RFC 6287](https://datatracker.ietf.org/doc/html/rfc6287), e.g. for
+//! signing a transaction by its amount or reference.
+
+use std::fmt;
+
+use sha1::{Digest, Sha1};
+use sha2::{Sha256, Sha512};
+use unix_time::Instant;
+
+use crate::{dynamic_truncation, Algorithm, Encoding, HotpError, KeyedHmac, OtpResult};
+
+/// The data described by an OCRA suite, other than the challenge
+/// question, which every suite requires.
+///
+/// Inputs which the suite does not use are ignored.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OcraInput<'a> {
+    /// The counter, for suites with a `C` data input.
+    pub counter: Option<u64>,
+    /// The challenge question, e.g. the digits of an amount for a `QN`
+    /// suite.
+    pub question: &'a str,
+    /// The user's PIN or password, which is hashed as the suite's `P`
+    /// data input requires.
+    pub password: Option<&'a [u8]>,
+    /// Information about the session, for suites with an `S` data
+    /// input. It is padded with leading zeroes to the suite's length.
+    pub session: Option<&'a [u8]>,
+    /// The time, for suites with a `T` data input.
+    pub time: Option<Instant>,
+}
+
+/// The OCRA challenge-response generator for a suite such as
+/// `OCRA-1:HOTP-SHA1-6:QN08`.
+///
+/// ```
+/// use otp2::Ocra;
+///
+/// let ocra = Ocra::new(b"12345678901234567890", "OCRA-1:HOTP-SHA1-6:QN08")?;
+/// assert_eq!(ocra.generate("00000000")?, "237653");
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+#[derive(Clone)]
+pub struct Ocra {
+    hmac: KeyedHmac,
+    suite: Suite,
+}
+
+impl fmt::Debug for Ocra {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ocra")
+            .field("hmac", &"<redacted>")
+            .field("suite", &self.suite.name)
+            .finish()
+    }
+}
+
+impl Ocra {
+    /// Get an OCRA generator with the given `key` for `suite`.
+    ///
+    /// Fails if the suite is malformed, or truncates to fewer than 4 or
+    /// more than [`MAX_DIGITS`](crate::MAX_DIGITS) digits.
+    pub fn new(key: impl Into<Vec<u8>>, suite: &str) -> OtpResult<Self> {
+        let suite = Suite::parse(suite)?;
+        let key = zeroize::Zeroizing::new(key.into());
+        Ok(Ocra {
+            hmac: KeyedHmac::new(suite.algorithm, &key),
+            suite,
+        })
+    }
+
+    /// The suite the generator was constructed with.
+    pub fn suite(&self) -> &str {
+        &self.suite.name
+    }
+
+    /// Respond to the challenge `question`, for suites whose only data
+    /// input is the challenge, e.g. `OCRA-1:HOTP-SHA1-6:QN08`.
+    ///
+    /// See [`Self::generate_with`].
+    pub fn generate(&self, question: &str) -> OtpResult<String> {
+        self.generate_with(&OcraInput {
+            question,
+            ..OcraInput::default()
+        })
+    }
+
+    /// Respond to the challenge with every data input of the suite.
+    ///
+    /// Fails if the question is malformed or too long for the suite, or
+    /// if an input required by the suite is missing.
+    pub fn generate_with(&self, input: &OcraInput<'_>) -> OtpResult<String> {
+        let data = self.suite.data_input(input)?;
+        let snum = dynamic_truncation(&self.hmac.compute(&data))?;
+        let encoding = Encoding::decimal();
+        // As in `Otp::compute`, 10 digits overflow a `u32` modulus.
+        let code = u64::from(snum) % encoding.modulus(self.suite.digits);
+        Ok(encoding.format(code as u32, self.suite.digits))
+    }
+}
+
+/// How the challenge question is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Question {
+    Alphanumeric,
+    Hex,
+    Numeric,
+}
+
+/// A parsed OCRA suite, `Algorithm:CryptoFunction:DataInput`.
+#[derive(Debug, Clone)]
+struct Suite {
+    name: String,
+    algorithm: Algorithm,
+    digits: u32,
+    counter: bool,
+    question: Question,
+    /// The maximum length of the question.
+    question_len: usize,
+    password: Option<Algorithm>,
+    /// The length in bytes of the session information.
+    session_len: Option<usize>,
+    /// The length in seconds of a timestamp step.
+    time_step: Option<u64>,
+}
+
+/// The questions are padded to 128 bytes.
+const QUESTION_BYTES: usize = 128;
+
+impl Suite {
+    fn parse(name: &str) -> OtpResult<Self> {
+        let mut parts = name.split(':');
+        let (Some("OCRA-1"), Some(function), Some(data), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid(format!("unsupported suite {name:?}")));
+        };
+
+        let (algorithm, digits) = function
+            .strip_prefix("HOTP-")
+            .and_then(|function| function.split_once('-'))
+            .ok_or_else(|| invalid(format!("unsupported crypto function {function:?}")))?;
+        let algorithm = hash(algorithm)?;
+        let digits = match digits.parse() {
+            Ok(digits @ 4..=crate::MAX_DIGITS) => digits,
+            _ => return Err(invalid(format!("unsupported digit count {digits:?}"))),
+        };
+
+        let mut inputs = data.split('-').peekable();
+        let counter = inputs.next_if_eq(&"C").is_some();
+        let question = inputs
+            .next()
+            .and_then(|input| input.strip_prefix('Q'))
+            .ok_or_else(|| invalid("missing challenge question"))?;
+        let (question, question_len) = match question.split_at_checked(1) {
+            Some(("A", len)) => (Question::Alphanumeric, len),
+            Some(("H", len)) => (Question::Hex, len),
+            Some(("N", len)) => (Question::Numeric, len),
+            _ => return Err(invalid(format!("unsupported question format Q{question}"))),
+        };
+        let question_len = match question_len.parse() {
+            Ok(len @ 4..=64) => len,
+            _ => {
+                return Err(invalid(format!(
+                    "unsupported question length {question_len:?}"
+                )))
+            }
+        };
+
+        let mut suite = Suite {
+            name: name.to_string(),
+            algorithm,
+            digits,
+            counter,
+            question,
+            question_len,
+            password: None,
+            session_len: None,
+            time_step: None,
+        };
+        for input in inputs {
+            match input.split_at_checked(1) {
+                Some(("P", algorithm)) if suite.password.is_none() => {
+                    suite.password = Some(hash(algorithm)?);
+                }
+                // The length is always three digits, e.g. S064.
+                Some(("S", len)) if suite.session_len.is_none() && len.len() == 3 => {
+                    suite.session_len = Some(number(len)? as usize);
+                }
+                // RFC 6287 allows 1-59 seconds or minutes, or up to 48
+                // hours. A step of zero hours would never advance.
+                Some(("T", step)) if suite.time_step.is_none() => {
+                    let (step, seconds, max) =
+                        match step.split_at_checked(step.len().saturating_sub(1)) {
+                            Some((step, "S")) => (step, 1, 59),
+                            Some((step, "M")) => (step, 60, 59),
+                            Some((step, "H")) => (step, 60 * 60, 48),
+                            _ => return Err(invalid(format!("unsupported time step {input:?}"))),
+                        };
+                    let step = number(step)?;
+                    let time_step = (step <= max)
+                        .then(|| step.checked_mul(seconds))
+                        .flatten()
+                        .ok_or_else(|| invalid(format!("time step out of range {input:?}")))?;
+                    suite.time_step = Some(time_step);
+                }
+                _ => return Err(invalid(format!("unsupported data input {input:?}"))),
+            }
+        }
+        Ok(suite)
+    }
+
+    /// Assemble the HMAC input: the suite, a zero byte, then each data
+    /// input of the suite in order.
+    fn data_input(&self, input: &OcraInput<'_>) -> OtpResult<Vec<u8>> {
+        let mut data = self.name.as_bytes().to_vec();
+        data.push(0);
+        if self.counter {
+            let counter = input.counter.ok_or_else(|| invalid("missing counter"))?;
+            data.extend(counter.to_be_bytes());
+        }
+        data.extend(self.question_bytes(input.question)?);
+        if let Some(algorithm) = self.password {
+            let password = input.password.ok_or_else(|| invalid("missing password"))?;
+            data.extend(match algorithm {
+                Algorithm::Sha1 => Sha1::digest(password).to_vec(),
+                Algorithm::Sha256 => Sha256::digest(password).to_vec(),
+                Algorithm::Sha512 => Sha512::digest(password).to_vec(),
+            });
+        }
+        if let Some(len) = self.session_len {
+            let session = input.session.ok_or_else(|| invalid("missing session"))?;
+            if session.len() > len {
+                return Err(invalid(format!("session is longer than {len} bytes")));
+            }
+            data.resize(data.len() + len - session.len(), 0);
+            data.extend(session);
+        }
+        if let Some(step) = self.time_step {
+            let time = input.time.ok_or_else(|| invalid("missing time"))?;
+            data.extend((time.secs() / step).to_be_bytes());
+        }
+        Ok(data)
+    }
+
+    /// The question, written left-aligned as 128 bytes. Numeric questions
+    /// are converted to hexadecimal first.
+    fn question_bytes(&self, question: &str) -> OtpResult<[u8; QUESTION_BYTES]> {
+        if question.is_empty() || question.len() > self.question_len {
+            return Err(invalid(format!(
+                "question must have between 1 and {} characters",
+                self.question_len
+            )));
+        }
+        let mut bytes = [0; QUESTION_BYTES];
+        match self.question {
+            Question::Alphanumeric => {
+                if !question.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                    return Err(invalid("question is not alphanumeric"));
+                }
+                bytes[..question.len()].copy_from_slice(question.as_bytes());
+            }
+            Question::Hex => write_nibbles(question, &mut bytes)?,
+            Question::Numeric => {
+                if !question.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid("question is not numeric"));
+                }
+                write_nibbles(&decimal_to_hex(question), &mut bytes)?;
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// The hash algorithm named in a suite, e.g. `SHA256`.
+fn hash(name: &str) -> OtpResult<Algorithm> {
+    match name {
+        "SHA1" => Ok(Algorithm::Sha1),
+        "SHA256" => Ok(Algorithm::Sha256),
+        "SHA512" => Ok(Algorithm::Sha512),
+        _ => Err(invalid(format!("unsupported hash function {name:?}"))),
+    }
+}
+
+fn number(digits: &str) -> OtpResult<u64> {
+    match digits.parse() {
+        Ok(0) | Err(_) => Err(invalid(format!(
+            "expected a positive number, not {digits:?}"
+        ))),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Write the hexadecimal digits of `hex` into the start of `bytes`, two
+/// to a byte, so an odd digit out fills the high half of its byte.
+fn write_nibbles(hex: &str, bytes: &mut [u8]) -> OtpResult<()> {
+    for (i, c) in hex.chars().enumerate() {
+        let nibble = c
+            .to_digit(16)
+            .ok_or_else(|| invalid("question is not hexadecimal"))?;
+        bytes[i / 2] |= (nibble as u8) << if i % 2 == 0 { 4 } else { 0 };
+    }
+    Ok(())
+}
+
+/// Convert a decimal number of any length to hexadecimal, without leading
+/// zeroes.
+fn decimal_to_hex(decimal: &str) -> String {
+    // Base-16 digits, least significant first.
+    let mut nibbles = vec![0u32];
+    for digit in decimal.bytes().map(|b| u32::from(b - b'0')) {
+        let mut carry = digit;
+        for nibble in &mut nibbles {
+            let value = *nibble * 10 + carry;
+            *nibble = value % 16;
+            carry = value / 16;
+        }
+        while carry > 0 {
+            nibbles.push(carry % 16);
+            carry /= 16;
+        }
+    }
+    while nibbles.len() > 1 && nibbles.last() == Some(&0) {
+        nibbles.pop();
+    }
+    nibbles
+        .iter()
+        .rev()
+        .map(|&n| char::from_digit(n, 16).expect("nibbles are below 16"))
+        .collect()
+}
+
+fn invalid(err: impl Into<String>) -> HotpError {
+    HotpError::InvalidOcra { err: err.into() }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+    use unix_time::Instant;
+
+    use crate::{
+        ocra::{decimal_to_hex, Ocra, OcraInput},
+        HotpError,
+    };
+
+    const KEY_20: &[u8] = b"12345678901234567890";
+    const KEY_32: &[u8] = b"12345678901234567890123456789012";
+    const KEY_64: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    // These test cases are copied from RFC 6287
+    // https://datatracker.ietf.org/doc/html/rfc6287#appendix-C.1
+    #[test_case("00000000", "237653")]
+    #[test_case("11111111", "243178")]
+    #[test_case("22222222", "653583")]
+    #[test_case("33333333", "740991")]
+    #[test_case("44444444", "608993")]
+    #[test_case("55555555", "388898")]
+    #[test_case("66666666", "816933")]
+    #[test_case("77777777", "224598")]
+    #[test_case("88888888", "750600")]
+    #[test_case("99999999", "294470")]
+    fn it_computes_challenge_only_vectors(question: &str, expected: &str) {
+        let ocra = Ocra::new(KEY_20, "OCRA-1:HOTP-SHA1-6:QN08").unwrap();
+        assert_eq!(ocra.generate(question).unwrap(), expected);
+    }
+
+    // Not in the RFC, so computed with Python's `hmac` module. The
+    // truncated value has 10 digits, so is not reduced.
+    #[test]
+    fn it_computes_ten_digit_responses() {
+        let ocra = Ocra::new(KEY_20, "OCRA-1:HOTP-SHA1-10:QN08").unwrap();
+        assert_eq!(ocra.generate("00000000").unwrap(), "1164947306");
+    }
+
+    #[test_case(0, "65347737")]
+    #[test_case(1, "86775851")]
+    fn it_computes_counter_and_password_vectors(counter: u64, expected: &str) {
+        let ocra = Ocra::new(KEY_32, "OCRA-1:HOTP-SHA256-8:C-QN08-PSHA1").unwrap();
+        let input = OcraInput {
+            counter: Some(counter),
+            question: "12345678",
+            password: Some(b"1234"),
+            ..OcraInput::default()
+        };
+        assert_eq!(ocra.generate_with(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn it_computes_timestamp_vectors() {
+        let ocra = Ocra::new(KEY_64, "OCRA-1:HOTP-SHA512-8:QN08-T1M").unwrap();
+        let input = OcraInput {
+            question: "00000000",
+            // 0x132d0b6 minutes after the epoch
+            time: Some(Instant::at(0x132d0b6 * 60 + 59, 0)),
+            ..OcraInput::default()
+        };
+        assert_eq!(ocra.generate_with(&input).unwrap(), "95209754");
+    }
+
+    #[test_case("0", "0")]
+    #[test_case("15", "f")]
+    #[test_case("00000256", "100")]
+    #[test_case("18446744073709551616", "10000000000000000")]
+    fn it_converts_decimal_to_hex(decimal: &str, expected: &str) {
+        assert_eq!(decimal_to_hex(decimal), expected);
+    }
+
+    #[test_case("OCRA-2:HOTP-SHA1-6:QN08" ; "unknown version")]
+    #[test_case("OCRA-1:HOTP-MD5-6:QN08" ; "unknown hash")]
+    #[test_case("OCRA-1:HOTP-SHA1-3:QN08" ; "too few digits")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:C" ; "missing question")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QX08" ; "unknown question format")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T1D" ; "unknown time unit")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T60S" ; "too many seconds")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T60M" ; "too many minutes")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T49H" ; "too many hours")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T0H" ; "zero hours")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T99999999999999999H" ; "overflowing time step")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-C" ; "counter out of order")]
+    fn it_rejects_malformed_suites(suite: &str) {
+        let result = Ocra::new(KEY_20, suite);
+        assert!(matches!(result, Err(HotpError::InvalidOcra { .. })));
+    }
+
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T59S")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T59M")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-T48H")]
+    fn it_accepts_the_longest_time_steps(suite: &str) {
+        assert!(Ocra::new(KEY_20, suite).is_ok());
+    }
+
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08", "123456789" ; "question too long")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08", "1234abcd" ; "question not numeric")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:C-QN08", "12345678" ; "missing counter")]
+    #[test_case("OCRA-1:HOTP-SHA1-6:QN08-PSHA1", "12345678" ; "missing password")]
+    fn it_rejects_invalid_input(suite: &str, question: &str) {
+        let ocra = Ocra::new(KEY_20, suite).unwrap();
+        let result = ocra.generate(question);
+        assert!(matches!(result, Err(HotpError::InvalidOcra { .. })));
+    }
+}
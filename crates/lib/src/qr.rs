@@ -0,0 +1,71 @@
+//! Rendering provisioning URIs as QR codes, for authenticator apps to
+//! scan when enrolling an account.
+
+use std::io::Cursor;
+
+use image::{ImageFormat, Luma};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use unix_time::Instant;
+
+use crate::{HotpError, OtpResult, Totp};
+
+impl<F: Fn() -> Instant> Totp<F> {
+    /// Render the provisioning URI written by [`Self::to_uri`] as a QR
+    /// code, returning the bytes of a PNG image.
+    pub fn qr_png(&self, issuer: &str, account: &str) -> OtpResult<Vec<u8>> {
+        let code = qr_code(&self.to_uri(issuer, account)?)?;
+        let image = code.render::<Luma<u8>>().build();
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| HotpError::Qr { err: e.to_string() })?;
+        Ok(png)
+    }
+
+    /// Render the provisioning URI written by [`Self::to_uri`] as a QR
+    /// code made of Unicode half-block characters, to be printed to a
+    /// terminal and scanned from there.
+    ///
+    /// Light modules are drawn as blocks, as suits the usual light text
+    /// on a dark background.
+    pub fn qr_terminal(&self, issuer: &str, account: &str) -> OtpResult<String> {
+        let code = qr_code(&self.to_uri(issuer, account)?)?;
+        Ok(code
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build())
+    }
+}
+
+fn qr_code(uri: &str) -> OtpResult<QrCode> {
+    QrCode::new(uri.as_bytes()).map_err(|e| HotpError::Qr { err: e.to_string() })
+}
+
+#[cfg(test)]
+mod test {
+    use unix_time::Instant;
+
+    use crate::Totp;
+
+    #[test]
+    fn it_renders_a_png() {
+        let otp = Totp::new("12345678901234567890", Instant::at(0, 0), 30, 6).unwrap();
+        let png = otp.qr_png("ACME Co", "john.doe@email.com").unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let image = image::load_from_memory(&png).unwrap();
+        assert_eq!(image.width(), image.height());
+    }
+
+    #[test]
+    fn it_renders_for_a_terminal() {
+        let otp = Totp::new("12345678901234567890", Instant::at(0, 0), 30, 6).unwrap();
+        let qr = otp.qr_terminal("ACME Co", "john.doe@email.com").unwrap();
+        let lines: Vec<&str> = qr.lines().collect();
+        // Each line holds two rows of modules
+        let width = lines[0].chars().count();
+        assert_eq!(lines.len(), width.div_ceil(2));
+        assert!(lines.iter().all(|line| line.chars().count() == width));
+        assert!(qr.chars().all(|c| " ▀▄█\n".contains(c)));
+    }
+}
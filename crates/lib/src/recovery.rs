@@ -0,0 +1,218 @@
+//! Single-use recovery codes, for when a user loses the device holding
+//! their one-time passcode secret.
+
+use core::fmt;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroizing;
+
+use crate::{HotpError, OtpResult};
+
+/// The symbols of a recovery code: digits and lowercase letters,
+/// without `i`, `l`, `o` and `u`, which are easily misread.
+const ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+/// The number of symbols in each of the two hyphenated groups of a
+/// recovery code, giving 50 bits of randomness.
+const GROUP_LENGTH: usize = 5;
+
+/// A set of single-use recovery codes, of which only HMAC-SHA256 hashes
+/// are kept, under a key generated at random for the set.
+///
+/// The codes themselves are returned once, by [`Self::generate`], to be
+/// shown to the user. Each can then be redeemed once with
+/// [`Self::consume`].
+///
+/// A code has only 50 bits of randomness, so anyone who has both the
+/// hashes and the [key](Self::key) can find the codes by trying each in
+/// turn. The key should therefore be stored apart from the hashes, e.g.
+/// encrypted under a key the database never sees, so that a leaked copy
+/// of the hashes alone reveals nothing. Even if they are stored together,
+/// the key acts as a salt: no table can be computed in advance, and the
+/// work of searching one set does not help with any other.
+///
+/// ```
+/// use otp2::RecoveryCodes;
+///
+/// let (mut recovery, codes) = RecoveryCodes::generate(10)?;
+/// assert!(recovery.consume(&codes[0]));
+/// assert!(!recovery.consume(&codes[0]));
+/// assert_eq!(recovery.remaining(), 9);
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+#[derive(Clone)]
+pub struct RecoveryCodes {
+    key: Zeroizing<[u8; KEY_LENGTH]>,
+    hashes: Vec<[u8; 32]>,
+}
+
+/// The length of the key each set of codes is hashed under, in bytes.
+const KEY_LENGTH: usize = 32;
+
+impl RecoveryCodes {
+    /// Generate `count` random codes, such as `"3kq7z-m0bxa"`, and the
+    /// key to hash them under, from the operating system's
+    /// cryptographically secure generator.
+    ///
+    /// Fails if the generator is unavailable.
+    pub fn generate(count: usize) -> OtpResult<(Self, Vec<String>)> {
+        let mut key = Zeroizing::new([0; KEY_LENGTH]);
+        getrandom::fill(&mut key[..]).map_err(|e| HotpError::Random { err: e.to_string() })?;
+        let mut random = vec![0; count * 2 * GROUP_LENGTH];
+        getrandom::fill(&mut random).map_err(|e| HotpError::Random { err: e.to_string() })?;
+        let codes: Vec<String> = random
+            .chunks(2 * GROUP_LENGTH)
+            .map(|symbols| {
+                // 256 is a multiple of 32, so each symbol is uniform.
+                let (first, second) = symbols.split_at(GROUP_LENGTH);
+                let group = |g: &[u8]| -> String {
+                    g.iter()
+                        .map(|&b| ALPHABET[usize::from(b % 32)] as char)
+                        .collect()
+                };
+                format!("{}-{}", group(first), group(second))
+            })
+            .collect();
+        let hashes = codes.iter().map(|code| hash(&key, code)).collect();
+        Ok((RecoveryCodes { key, hashes }, codes))
+    }
+
+    /// Restore a set of codes from the [key](Self::key) and the
+    /// [hashes](Self::hashes) they were hashed under, e.g. as read from a
+    /// database.
+    pub fn from_hashes(key: [u8; KEY_LENGTH], hashes: Vec<[u8; 32]>) -> Self {
+        RecoveryCodes {
+            key: Zeroizing::new(key),
+            hashes,
+        }
+    }
+
+    /// The key the codes are hashed under, for persisting apart from the
+    /// hashes.
+    pub fn key(&self) -> &[u8; KEY_LENGTH] {
+        &self.key
+    }
+
+    /// The hashes of the codes which have not yet been used, for
+    /// persisting.
+    pub fn hashes(&self) -> &[[u8; 32]] {
+        &self.hashes
+    }
+
+    /// The number of codes which have not yet been used.
+    pub fn remaining(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Redeem a submitted `code`, so that it cannot be used again.
+    ///
+    /// Case, hyphens and whitespace are ignored. Every remaining hash is
+    /// compared in constant time, so the time taken does not reveal
+    /// which code, if any, matched. Returns `false` for an unknown or
+    /// already used code.
+    pub fn consume(&mut self, code: &str) -> bool {
+        let submitted = hash(&self.key, code);
+        let mut found = Choice::from(0);
+        let mut matched = 0u64;
+        for (i, stored) in self.hashes.iter().enumerate() {
+            let equal = stored.ct_eq(&submitted);
+            matched.conditional_assign(&(i as u64), equal);
+            found |= equal;
+        }
+        if bool::from(found) {
+            self.hashes.swap_remove(matched as usize);
+        }
+        found.into()
+    }
+}
+
+/// Never print the key itself.
+impl fmt::Debug for RecoveryCodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecoveryCodes")
+            .field("key", &"<redacted>")
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+/// The HMAC-SHA256 of a code under `key`, after normalizing how it was
+/// typed.
+fn hash(key: &[u8; KEY_LENGTH], code: &str) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for c in code.chars().filter(|c| *c != '-' && !c.is_whitespace()) {
+        let mut buf = [0; 4];
+        mac.update(c.to_ascii_lowercase().encode_utf8(&mut buf).as_bytes());
+    }
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use crate::recovery::{RecoveryCodes, ALPHABET};
+
+    #[test]
+    fn it_generates_grouped_codes() {
+        let (recovery, codes) = RecoveryCodes::generate(8).unwrap();
+        assert_eq!(recovery.remaining(), 8);
+        assert_eq!(codes.len(), 8);
+        for code in &codes {
+            let (first, second) = code.split_once('-').unwrap();
+            assert_eq!((first.len(), second.len()), (5, 5));
+            assert!(first
+                .bytes()
+                .chain(second.bytes())
+                .all(|b| ALPHABET.contains(&b)));
+        }
+        assert_ne!(codes[0], codes[1]);
+    }
+
+    #[test]
+    fn it_consumes_each_code_once() {
+        let (mut recovery, codes) = RecoveryCodes::generate(3).unwrap();
+        assert!(recovery.consume(&codes[1]));
+        assert!(!recovery.consume(&codes[1]));
+        assert!(recovery.consume(&codes[0]));
+        assert!(recovery.consume(&codes[2]));
+        assert_eq!(recovery.remaining(), 0);
+    }
+
+    #[test_case(|code| code.to_uppercase() ; "upper case")]
+    #[test_case(|code| code.replace('-', "") ; "without the hyphen")]
+    #[test_case(|code| code.replace('-', " ") ; "with a space")]
+    fn it_ignores_formatting(typed: fn(&str) -> String) {
+        let (mut recovery, codes) = RecoveryCodes::generate(1).unwrap();
+        assert!(recovery.consume(&typed(&codes[0])));
+    }
+
+    #[test]
+    fn it_rejects_unknown_codes() {
+        let (mut recovery, _) = RecoveryCodes::generate(3).unwrap();
+        assert!(!recovery.consume("00000-00000"));
+        assert!(!recovery.consume(""));
+        assert_eq!(recovery.remaining(), 3);
+    }
+
+    #[test]
+    fn it_restores_from_hashes() {
+        let (recovery, codes) = RecoveryCodes::generate(2).unwrap();
+        let mut restored = RecoveryCodes::from_hashes(*recovery.key(), recovery.hashes().to_vec());
+        assert!(restored.consume(&codes[1]));
+        assert_eq!(restored.hashes(), &recovery.hashes()[..1]);
+    }
+
+    #[test]
+    fn it_hashes_each_set_under_its_own_key() {
+        let (first, codes) = RecoveryCodes::generate(1).unwrap();
+        let (second, _) = RecoveryCodes::generate(1).unwrap();
+        assert_ne!(first.key(), second.key());
+        // The right hashes are no use without the right key.
+        let mut wrong_key = RecoveryCodes::from_hashes(*second.key(), first.hashes().to_vec());
+        assert!(!wrong_key.consume(&codes[0]));
+        assert!(!format!("{first:?}").contains(&format!("{:?}", first.key())));
+    }
+}
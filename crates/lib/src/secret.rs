@@ -0,0 +1,71 @@
+//! Generating fresh secrets to enroll an account with.
+
+use crate::{base32, check_key_length, HotpError, OtpResult, Totp};
+
+impl Totp {
+    /// Generate a secret of `len_bytes` random bytes from the operating
+    /// system's cryptographically secure generator.
+    ///
+    /// RFC 4226 recommends [`DEFAULT_SECRET_LENGTH`](crate::DEFAULT_SECRET_LENGTH)
+    /// bytes. Fails if `len_bytes` is below
+    /// [`MIN_KEY_LENGTH`](crate::MIN_KEY_LENGTH), or if the generator is
+    /// unavailable.
+    ///
+    /// ```
+    /// use otp2::{Totp, DEFAULT_SECRET_LENGTH};
+    ///
+    /// let secret = Totp::generate_secret(DEFAULT_SECRET_LENGTH)?;
+    /// let totp = Totp::new_strict(secret, unix_time::Instant::at(0, 0), 30, 6)?;
+    /// # Ok::<(), otp2::HotpError>(())
+    /// ```
+    pub fn generate_secret(len_bytes: usize) -> OtpResult<Vec<u8>> {
+        let mut secret = vec![0; len_bytes];
+        check_key_length(&secret)?;
+        getrandom::fill(&mut secret).map_err(|e| HotpError::Random { err: e.to_string() })?;
+        Ok(secret)
+    }
+
+    /// Generate a secret as by [`Self::generate_secret`], encoded as
+    /// unpadded Base32 for a provisioning URI. To show the secret to
+    /// users, see [`format_base32`](crate::format_base32).
+    pub fn generate_secret_base32(len_bytes: usize) -> OtpResult<String> {
+        Ok(base32::encode(&Totp::generate_secret(len_bytes)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use crate::{base32, HotpError, Totp, DEFAULT_SECRET_LENGTH};
+
+    #[test_case(16)]
+    #[test_case(DEFAULT_SECRET_LENGTH)]
+    #[test_case(64)]
+    fn it_generates_secrets_of_the_given_length(len: usize) {
+        assert_eq!(Totp::generate_secret(len).unwrap().len(), len);
+    }
+
+    #[test]
+    fn it_generates_different_secrets() {
+        let a = Totp::generate_secret(DEFAULT_SECRET_LENGTH).unwrap();
+        let b = Totp::generate_secret(DEFAULT_SECRET_LENGTH).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn it_rejects_short_secrets() {
+        let result = Totp::generate_secret(15);
+        assert!(matches!(result, Err(HotpError::WeakKey { length: 15 })));
+    }
+
+    #[test]
+    fn it_encodes_generated_secrets_as_base32() {
+        let secret = Totp::generate_secret_base32(DEFAULT_SECRET_LENGTH).unwrap();
+        assert_eq!(secret.len(), 32);
+        assert_eq!(
+            base32::decode(&secret).unwrap().len(),
+            DEFAULT_SECRET_LENGTH
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! A collection of TOTP generators, one per account, as kept by an
+//! authenticator app.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use unix_time::Instant;
+
+use crate::{OtpResult, Totp};
+
+/// Identifies an account in an [`OtpStore`], by the service it belongs
+/// to and the user's name for it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AccountKey {
+    pub issuer: Option<String>,
+    pub account: String,
+}
+
+impl AccountKey {
+    pub fn new(issuer: Option<&str>, account: &str) -> Self {
+        AccountKey {
+            issuer: issuer.map(String::from),
+            account: account.into(),
+        }
+    }
+}
+
+/// Formats as `issuer:account`, or just the account when there is no
+/// issuer, as in the label of a provisioning URI.
+impl fmt::Display for AccountKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.issuer {
+            Some(issuer) => write!(f, "{issuer}:{}", self.account),
+            None => f.write_str(&self.account),
+        }
+    }
+}
+
+/// TOTP generators for many accounts, ordered by [`AccountKey`].
+///
+/// With the `serde` feature the store serializes as a list of
+/// `TotpConfig`s, so that it can be persisted.
+#[derive(Debug, Default)]
+pub struct OtpStore {
+    accounts: BTreeMap<AccountKey, Totp>,
+}
+
+impl OtpStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the generator for an account, labelling it with `key`.
+    ///
+    /// Returns the generator previously stored for the account, if any.
+    pub fn add(&mut self, key: AccountKey, mut totp: Totp) -> Option<Totp> {
+        totp.issuer = key.issuer.clone();
+        totp.account = Some(key.account.clone());
+        self.accounts.insert(key, totp)
+    }
+
+    /// Remove an account, returning its generator.
+    pub fn remove(&mut self, key: &AccountKey) -> Option<Totp> {
+        self.accounts.remove(key)
+    }
+
+    /// The generator for an account.
+    pub fn get(&self, key: &AccountKey) -> Option<&Totp> {
+        self.accounts.get(key)
+    }
+
+    /// The passcode for an account's current window, with leading
+    /// zeroes, or `None` if there is no such account.
+    pub fn get_code(&self, key: &AccountKey) -> Option<OtpResult<String>> {
+        self.get(key).map(|totp| code_at(totp, Instant::now()))
+    }
+
+    /// The passcodes of every account for their current windows, in
+    /// order of [`AccountKey`].
+    ///
+    /// The codes are all computed for the same instant, so none expires
+    /// midway through.
+    pub fn all_codes(&self) -> impl Iterator<Item = (&AccountKey, OtpResult<String>)> {
+        let now = Instant::now();
+        self.accounts
+            .iter()
+            .map(move |(key, totp)| (key, code_at(totp, now)))
+    }
+
+    /// The accounts, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &AccountKey> {
+        self.accounts.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+fn code_at(totp: &Totp, at: Instant) -> OtpResult<String> {
+    let code = totp.get_at(at)?;
+    Ok(totp.encoding.format(code, totp.width()))
+}
+
+#[cfg(feature = "serde")]
+mod persist {
+    use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{
+        store::{AccountKey, OtpStore},
+        TotpConfig,
+    };
+
+    /// Fails for a generator whose HMAC is computed externally, since
+    /// its secret is unknown.
+    impl Serialize for OtpStore {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let configs = self
+                .accounts
+                .values()
+                .map(|totp| totp.to_config())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(ser::Error::custom)?;
+            configs.serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for OtpStore {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let mut store = OtpStore::new();
+            for config in Vec::<TotpConfig>::deserialize(deserializer)? {
+                let key = AccountKey {
+                    issuer: config.issuer.clone(),
+                    account: config.account.clone().unwrap_or_default(),
+                };
+                store.add(key, config.build().map_err(de::Error::custom)?);
+            }
+            Ok(store)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use unix_time::Instant;
+
+    use crate::{
+        store::{AccountKey, OtpStore},
+        Totp,
+    };
+
+    fn totp(key: &str, digits: u32) -> Totp {
+        Totp::new(key, Instant::at(0, 0), 30, digits).unwrap()
+    }
+
+    #[test]
+    fn it_keeps_a_generator_per_account() {
+        let github = AccountKey::new(Some("GitHub"), "alice");
+        let email = AccountKey::new(None, "alice@example.com");
+        let mut store = OtpStore::new();
+        assert!(store
+            .add(github.clone(), totp("12345678901234567890", 6))
+            .is_none());
+        assert!(store
+            .add(email.clone(), totp("abcdefghijabcdefghij", 8))
+            .is_none());
+        assert_eq!(store.len(), 2);
+
+        let code = store.get_code(&github).unwrap().unwrap();
+        assert_eq!(code.len(), 6);
+        assert!(store.get(&github).unwrap().verify_str(&code));
+        assert_eq!(store.get(&github).unwrap().issuer(), Some("GitHub"));
+
+        let codes: Vec<_> = store
+            .all_codes()
+            .map(|(key, code)| (key.to_string(), code.unwrap().len()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                ("alice@example.com".to_string(), 8),
+                ("GitHub:alice".to_string(), 6)
+            ]
+        );
+
+        assert!(store.remove(&email).is_some());
+        assert!(store.get_code(&email).is_none());
+        assert_eq!(store.keys().collect::<Vec<_>>(), [&github]);
+    }
+
+    #[test]
+    fn it_replaces_an_account() {
+        let key = AccountKey::new(Some("ACME Co"), "bob");
+        let mut store = OtpStore::new();
+        store.add(key.clone(), totp("12345678901234567890", 6));
+        let old = store
+            .add(key.clone(), totp("12345678901234567890", 8))
+            .unwrap();
+        assert_eq!(old.digits, 6);
+        assert_eq!(store.get(&key).unwrap().digits, 8);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_round_trips_through_serde() {
+        let key = AccountKey::new(Some("ACME Co"), "bob");
+        let mut store = OtpStore::new();
+        store.add(key.clone(), totp("12345678901234567890", 8));
+        store.add(
+            AccountKey::new(None, "carol"),
+            totp("abcdefghijabcdefghij", 6),
+        );
+
+        let json = serde_json::to_string(&store).unwrap();
+        let restored: OtpStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.keys().collect::<Vec<_>>(),
+            store.keys().collect::<Vec<_>>()
+        );
+        let at = Instant::at(59, 0);
+        assert_eq!(restored.get(&key).unwrap().get_at(at).unwrap(), 94287082);
+    }
+}
@@ -0,0 +1,271 @@
+//! Locking out verification after repeated failures, to resist online
+//! guessing of passcodes.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use unix_time::Instant as UnixInstant;
+
+use crate::{Hotp, Totp};
+
+/// The outcome of [`RateLimiter::verify_throttled`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The code was valid, and the failures so far are forgotten.
+    Accepted,
+    /// The code was not valid, but more attempts are allowed.
+    Rejected,
+    /// Too many attempts have failed, so no code is checked until
+    /// `retry_after` has passed.
+    Locked { retry_after: Duration },
+}
+
+/// A generator whose verification refuses to check any code once too
+/// many have failed within a sliding window, so that the million 6-digit
+/// passcodes can't be tried one after another.
+///
+/// Each account should have its own limiter, so that failures for one
+/// don't lock out another. By default 5 failures within 5 minutes lock
+/// verification for 5 minutes.
+///
+/// ```
+/// use otp2::{RateLimiter, TotpBuilder, Verdict};
+///
+/// let totp = TotpBuilder::new().secret("12345678901234567890").build()?;
+/// let mut limiter = RateLimiter::new(totp).max_failures(2);
+/// let wrong = (limiter.get_ref().current()? + 1) % 1_000_000;
+/// assert_eq!(limiter.verify_throttled(wrong, 0), Verdict::Rejected);
+/// assert!(matches!(
+///     limiter.verify_throttled(wrong, 0),
+///     Verdict::Locked { .. }
+/// ));
+/// # Ok::<(), otp2::HotpError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter<T> {
+    otp: T,
+    max_failures: usize,
+    window: Duration,
+    cooldown: Duration,
+    /// When each failure within the window happened, oldest first.
+    failures: VecDeque<Instant>,
+    locked_until: Option<Instant>,
+}
+
+const DEFAULT_MAX_FAILURES: usize = 5;
+const DEFAULT_WINDOW: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+impl<T> RateLimiter<T> {
+    /// Limit attempts to verify codes of `otp`.
+    pub fn new(otp: T) -> Self {
+        RateLimiter {
+            otp,
+            max_failures: DEFAULT_MAX_FAILURES,
+            window: DEFAULT_WINDOW,
+            cooldown: DEFAULT_COOLDOWN,
+            failures: VecDeque::new(),
+            locked_until: None,
+        }
+    }
+
+    /// The number of failures within the window which locks verification.
+    /// At least one failure is always needed.
+    pub fn max_failures(mut self, max_failures: usize) -> Self {
+        self.max_failures = max_failures.max(1);
+        self
+    }
+
+    /// How far back failures are counted.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// How long verification stays locked.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// The wrapped generator.
+    pub fn get_ref(&self) -> &T {
+        &self.otp
+    }
+
+    /// Unwrap the generator, forgetting the failures.
+    pub fn into_inner(self) -> T {
+        self.otp
+    }
+
+    /// Check a code with `verify` unless verification is locked, and
+    /// record the outcome.
+    fn attempt(&mut self, now: Instant, verify: impl FnOnce(&mut T) -> bool) -> Verdict {
+        if let Some(until) = self.locked_until {
+            if now < until {
+                return Verdict::Locked {
+                    retry_after: until - now,
+                };
+            }
+            self.locked_until = None;
+            self.failures.clear();
+        }
+        while self
+            .failures
+            .front()
+            .is_some_and(|&failure| now.duration_since(failure) >= self.window)
+        {
+            self.failures.pop_front();
+        }
+        if verify(&mut self.otp) {
+            self.failures.clear();
+            return Verdict::Accepted;
+        }
+        self.failures.push_back(now);
+        if self.failures.len() < self.max_failures {
+            return Verdict::Rejected;
+        }
+        self.failures.clear();
+        self.locked_until = Some(now + self.cooldown);
+        Verdict::Locked {
+            retry_after: self.cooldown,
+        }
+    }
+}
+
+impl<F: Fn() -> UnixInstant> RateLimiter<Totp<F>> {
+    /// Check a submitted `code` as by [`Totp::verify_with_skew`], unless
+    /// too many attempts have failed.
+    ///
+    /// The failure which reaches the limit is reported as
+    /// [`Verdict::Locked`], as is every attempt until the cooldown is
+    /// over, whether or not its code is valid.
+    pub fn verify_throttled(&mut self, code: u32, skew: u64) -> Verdict {
+        self.verify_throttled_at(code, skew, Instant::now())
+    }
+
+    fn verify_throttled_at(&mut self, code: u32, skew: u64, now: Instant) -> Verdict {
+        self.attempt(now, |totp| totp.verify_with_skew(code, skew))
+    }
+}
+
+impl RateLimiter<Hotp> {
+    /// Check a submitted `code` as by [`Hotp::verify`], advancing the
+    /// counter if it is valid, unless too many attempts have failed.
+    ///
+    /// As with TOTP, the failure which reaches the limit is reported as
+    /// [`Verdict::Locked`], as is every attempt until the cooldown is over.
+    pub fn verify_throttled(&mut self, code: u32, look_ahead: u64) -> Verdict {
+        self.verify_throttled_at(code, look_ahead, Instant::now())
+    }
+
+    fn verify_throttled_at(&mut self, code: u32, look_ahead: u64, now: Instant) -> Verdict {
+        self.attempt(now, |hotp| hotp.verify(code, look_ahead).is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use unix_time::Instant as UnixInstant;
+
+    use crate::{Hotp, RateLimiter, Totp, Verdict};
+
+    const KEY: &str = "12345678901234567890";
+
+    /// A limiter on the RFC 6238 generator at T = 59, whose code is
+    /// 94287082, allowing 3 failures a minute.
+    fn limiter() -> RateLimiter<Totp<Box<dyn Fn() -> UnixInstant>>> {
+        let now: Box<dyn Fn() -> UnixInstant> = Box::new(|| UnixInstant::at(59, 0));
+        let totp = Totp::new_with_now(KEY, UnixInstant::at(0, 0), 30, 8, now).unwrap();
+        RateLimiter::new(totp)
+            .max_failures(3)
+            .window(Duration::from_secs(60))
+            .cooldown(Duration::from_secs(300))
+    }
+
+    #[test]
+    fn it_locks_after_too_many_failures() {
+        let mut limiter = limiter();
+        let start = Instant::now();
+        assert_eq!(limiter.verify_throttled_at(1, 0, start), Verdict::Rejected);
+        assert_eq!(limiter.verify_throttled_at(2, 0, start), Verdict::Rejected);
+        assert_eq!(
+            limiter.verify_throttled_at(3, 0, start),
+            Verdict::Locked {
+                retry_after: Duration::from_secs(300)
+            }
+        );
+        // Even the right code is refused while locked.
+        let later = start + Duration::from_secs(100);
+        assert_eq!(
+            limiter.verify_throttled_at(94287082, 0, later),
+            Verdict::Locked {
+                retry_after: Duration::from_secs(200)
+            }
+        );
+    }
+
+    #[test]
+    fn it_unlocks_after_the_cooldown() {
+        let mut limiter = limiter();
+        let start = Instant::now();
+        for code in 1..=3 {
+            limiter.verify_throttled_at(code, 0, start);
+        }
+        let after = start + Duration::from_secs(300);
+        assert_eq!(limiter.verify_throttled_at(1, 0, after), Verdict::Rejected);
+        assert_eq!(
+            limiter.verify_throttled_at(94287082, 0, after),
+            Verdict::Accepted
+        );
+    }
+
+    #[test]
+    fn it_forgets_failures_on_success() {
+        let mut limiter = limiter();
+        let start = Instant::now();
+        limiter.verify_throttled_at(1, 0, start);
+        limiter.verify_throttled_at(2, 0, start);
+        assert_eq!(
+            limiter.verify_throttled_at(94287082, 0, start),
+            Verdict::Accepted
+        );
+        assert_eq!(limiter.verify_throttled_at(3, 0, start), Verdict::Rejected);
+        assert_eq!(limiter.verify_throttled_at(4, 0, start), Verdict::Rejected);
+    }
+
+    #[test]
+    fn it_only_counts_failures_within_the_window() {
+        let mut limiter = limiter();
+        let start = Instant::now();
+        limiter.verify_throttled_at(1, 0, start);
+        limiter.verify_throttled_at(2, 0, start + Duration::from_secs(30));
+        // The first failure has left the window.
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.verify_throttled_at(3, 0, later), Verdict::Rejected);
+        assert!(matches!(
+            limiter.verify_throttled_at(4, 0, later),
+            Verdict::Locked { .. }
+        ));
+    }
+
+    #[test]
+    fn it_throttles_hotp() {
+        let hotp = Hotp::new(KEY, 0, 6).unwrap();
+        let mut limiter = RateLimiter::new(hotp).max_failures(1);
+        let start = Instant::now();
+        assert_eq!(
+            limiter.verify_throttled_at(755224, 0, start),
+            Verdict::Accepted
+        );
+        assert_eq!(limiter.get_ref().count(), 1);
+        assert!(matches!(
+            limiter.verify_throttled_at(755224, 0, start),
+            Verdict::Locked { .. }
+        ));
+    }
+}
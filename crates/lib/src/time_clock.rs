@@ -0,0 +1,103 @@
+//! Interop with the [`time`] crate, for applications which already keep
+//! time as an `OffsetDateTime`.
+
+use time::OffsetDateTime;
+use unix_time::Instant;
+
+use crate::{OtpResult, Totp};
+
+impl Totp {
+    /// Get a TOTP generator counting steps from an [`OffsetDateTime`],
+    /// with [`OffsetDateTime::now_utc`] providing the "now" value.
+    ///
+    /// The time is converted by its Unix timestamp, so the offset makes
+    /// no difference. Times before the epoch are treated as the epoch
+    /// itself.
+    ///
+    /// See [`Self::new`].
+    pub fn new_from_offset_datetime(
+        key: impl Into<Vec<u8>>,
+        t0: OffsetDateTime,
+        step: u64,
+        length: u32,
+    ) -> OtpResult<Self> {
+        Totp::new_with_now(
+            key,
+            instant_from_offset_datetime(t0),
+            step,
+            length,
+            time_now,
+        )
+    }
+
+    /// Get a TOTP generator counting steps from an [`OffsetDateTime`],
+    /// with a custom function providing the "now" value.
+    ///
+    /// See [`Self::new_from_offset_datetime`].
+    pub fn new_from_offset_datetime_with_now<N: Fn() -> OffsetDateTime>(
+        key: impl Into<Vec<u8>>,
+        t0: OffsetDateTime,
+        step: u64,
+        length: u32,
+        now: N,
+    ) -> OtpResult<Totp<impl Fn() -> Instant>> {
+        let now = move || instant_from_offset_datetime(now());
+        Totp::new_with_now(key, instant_from_offset_datetime(t0), step, length, now)
+    }
+}
+
+fn time_now() -> Instant {
+    instant_from_offset_datetime(OffsetDateTime::now_utc())
+}
+
+fn instant_from_offset_datetime(time: OffsetDateTime) -> Instant {
+    match u64::try_from(time.unix_timestamp()) {
+        Ok(secs) => Instant::at(secs, time.nanosecond()),
+        Err(_) => Instant::at(0, 0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::{OffsetDateTime, UtcOffset};
+    use unix_time::Instant;
+
+    use crate::{time_clock::instant_from_offset_datetime, Totp};
+
+    #[test]
+    fn it_computes_a_code_at_a_fixed_datetime() {
+        // From RFC 6238 appendix B, given in another offset
+        let offset = UtcOffset::from_hms(1, 0, 0).unwrap();
+        let now = || {
+            OffsetDateTime::from_unix_timestamp(1111111109)
+                .unwrap()
+                .to_offset(offset)
+        };
+        let mut otp = Totp::new_from_offset_datetime_with_now(
+            "12345678901234567890",
+            OffsetDateTime::UNIX_EPOCH,
+            30,
+            8,
+            now,
+        )
+        .unwrap();
+        assert_eq!(otp.get().unwrap(), 7081804);
+    }
+
+    #[test]
+    fn it_converts_datetimes_by_their_timestamp() {
+        let t0 = OffsetDateTime::from_unix_timestamp_nanos(1_000_000_000_000_000_500).unwrap();
+        assert_eq!(
+            instant_from_offset_datetime(t0),
+            Instant::at(1_000_000_000, 500)
+        );
+        let otp = Totp::new_from_offset_datetime("12345678901234567890", t0, 30, 8).unwrap();
+        assert_eq!(otp.generator.t0, Instant::at(1_000_000_000, 500));
+    }
+
+    #[test]
+    fn it_clamps_datetimes_before_the_epoch() {
+        let t0 = OffsetDateTime::from_unix_timestamp(-60).unwrap();
+        assert_eq!(instant_from_offset_datetime(t0), Instant::at(0, 0));
+    }
+}
@@ -1,68 +1,806 @@
-use crate::{Otp, OtpResult, ToBytes};
+use std::{
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+use crate::{
+    base32, check_digits, check_key_length, codes_equal, constant_time_eq, find_match, uri,
+    Algorithm, Encoding, HotpError, Key, MacFn, Otp, OtpResult, ToBytes,
+};
+
+use subtle::{Choice, ConstantTimeEq};
 use unix_time::Instant;
+use zeroize::Zeroizing;
 
 /// Time-based one-time passcode.
 ///
 /// Provides one-time passcodes that are valid within a window
 /// of time after the passcode is generated.
-pub type Totp = Otp<Time>;
+///
+/// The function `F` provides the "now" value. Most generators use the
+/// system clock through a plain function pointer, so need no
+/// allocation; a custom clock is stored as its own type.
+pub type Totp<F = fn() -> Instant> = Otp<Time<F>>;
+
+/// A boxed function providing the "now" value for a [`Totp`]
+/// generator, for when generators with different clocks must have the
+/// same type.
+pub type NowFn = Box<dyn Fn() -> Instant + Send + Sync>;
+
+/// The system clock, as the default "now" function.
+pub(crate) const SYSTEM_NOW: fn() -> Instant = Instant::now;
 
 impl Totp {
     /// Get a TOTP generator.
     ///
     /// Repeated calls to [`Self::get`] will return the same
-    /// passcode when in the same `window`.
-    pub fn new(key: String, t0: Instant, window: u64, length: u32) -> Self {
-        Totp::new_with_now(key, t0, window, length, Box::new(|| Instant::now()))
+    /// passcode when in the same `window`. The `key` is an arbitrary
+    /// byte string and need not be valid UTF-8.
+    ///
+    /// Fails if `length` is not between 1 and [`MAX_DIGITS`](crate::MAX_DIGITS),
+    /// or if `window` is zero.
+    pub fn new(key: impl Into<Vec<u8>>, t0: Instant, window: u64, length: u32) -> OtpResult<Self> {
+        Totp::new_with_now(key, t0, window, length, SYSTEM_NOW)
+    }
+
+    /// Get a TOTP generator whose windows last `step`.
+    ///
+    /// Fails if `step` is not a whole number of seconds, e.g.
+    /// `Duration::from_millis(1500)`, or is zero.
+    ///
+    /// See [`Self::new`].
+    pub fn new_with_duration(
+        key: impl Into<Vec<u8>>,
+        t0: Instant,
+        step: Duration,
+        length: u32,
+    ) -> OtpResult<Self> {
+        Totp::new(key, t0, whole_seconds(step)?, length)
+    }
+
+    /// Get a TOTP generator, rejecting keys shorter than
+    /// [`MIN_KEY_LENGTH`](crate::MIN_KEY_LENGTH) bytes.
+    ///
+    /// See [`Self::new`].
+    pub fn new_strict(
+        key: impl Into<Vec<u8>>,
+        t0: Instant,
+        window: u64,
+        length: u32,
+    ) -> OtpResult<Self> {
+        let key = key.into();
+        check_key_length(&key)?;
+        Totp::new(key, t0, window, length)
+    }
+
+    /// Get a TOTP generator from a Base32-encoded `secret`, as found in
+    /// provisioning URIs and authenticator apps.
+    ///
+    /// See [`Self::new`].
+    pub fn from_base32(secret: &str, t0: Instant, step: u64, length: u32) -> OtpResult<Self> {
+        let key = base32::decode(secret)?;
+        Totp::new(key, t0, step, length)
+    }
+
+    /// Get a TOTP generator from a hex-encoded `secret`, as the RFC
+    /// appendices express it.
+    ///
+    /// See [`Self::new`].
+    pub fn from_hex(secret: &str, t0: Instant, step: u64, length: u32) -> OtpResult<Self> {
+        let key = hex::decode(secret)?;
+        Totp::new(key, t0, step, length)
+    }
+
+    /// Get a TOTP generator from an `otpauth://totp/` provisioning URI,
+    /// as scanned from an enrollment QR code. The issuer and account
+    /// are kept, see [`Self::issuer`] and [`Self::account`].
+    ///
+    /// The digits, period and algorithm default to 6, 30 seconds and
+    /// SHA-1 when the URI does not give them. Fails if the URI is
+    /// malformed or is not for TOTP.
+    pub fn from_uri(uri: &str) -> OtpResult<Self> {
+        let parsed = uri::parse(uri, "totp")?;
+        let mut totp = Totp::new_with_algorithm(
+            parsed.secret,
+            Instant::at(0, 0),
+            parsed.period,
+            parsed.digits,
+            parsed.algorithm,
+            SYSTEM_NOW,
+        )?;
+        totp.issuer = parsed.issuer;
+        totp.account = Some(parsed.account);
+        Ok(totp)
+    }
+
+    /// Get a TOTP generator which uses SHA-256 to compute the HMAC.
+    ///
+    /// See [`Self::new`].
+    pub fn new_sha256(
+        key: impl Into<Vec<u8>>,
+        t0: Instant,
+        step: u64,
+        length: u32,
+    ) -> OtpResult<Self> {
+        Totp::new_with_algorithm(key, t0, step, length, Algorithm::Sha256, SYSTEM_NOW)
+    }
+
+    /// Get a TOTP generator which uses SHA-512 to compute the HMAC.
+    ///
+    /// See [`Self::new`].
+    pub fn new_sha512(
+        key: impl Into<Vec<u8>>,
+        t0: Instant,
+        step: u64,
+        length: u32,
+    ) -> OtpResult<Self> {
+        Totp::new_with_algorithm(key, t0, step, length, Algorithm::Sha512, SYSTEM_NOW)
+    }
+
+    /// Get a Steam Guard generator.
+    ///
+    /// Steam uses the usual SHA-1 TOTP with 30 second steps from the Unix
+    /// epoch, but encodes the passcode as 5 symbols from its own
+    /// alphabet, so use [`Self::get_string`] rather than [`Self::get`].
+    pub fn new_steam(key: impl Into<Vec<u8>>) -> OtpResult<Self> {
+        Totp::new_steam_with_now(key, SYSTEM_NOW)
+    }
+
+    /// Get a Steam Guard generator with a custom function to provide the
+    /// "now" value.
+    ///
+    /// See [`Self::new_steam`].
+    pub fn new_steam_with_now<F: Fn() -> Instant>(
+        key: impl Into<Vec<u8>>,
+        now: F,
+    ) -> OtpResult<Totp<F>> {
+        let mut totp = Totp::new_with_now(key, Instant::at(0, 0), 30, 5, now)?;
+        totp.encoding = Encoding::steam();
+        Ok(totp)
     }
 
     /// Get a TOTP generator with a custom function to provide the
     /// "now" value.
     ///
     /// See [`Self::new`].
-    pub fn new_with_now(
-        key: String,
+    pub fn new_with_now<F: Fn() -> Instant>(
+        key: impl Into<Vec<u8>>,
         t0: Instant,
         step: u64,
         digits: u32,
-        now: Box<dyn Fn() -> Instant>,
-    ) -> Self {
-        Otp {
-            key,
-            generator: Time { t0, step, now },
+        now: F,
+    ) -> OtpResult<Totp<F>> {
+        Totp::new_with_algorithm(key, t0, step, digits, Algorithm::Sha1, now)
+    }
+
+    /// Get a TOTP generator counting steps from a [`SystemTime`] rather
+    /// than an [`Instant`].
+    ///
+    /// The time is converted by its duration since the Unix epoch, so
+    /// `UNIX_EPOCH` is `Instant::at(0, 0)`. Times before the epoch are
+    /// treated as the epoch itself.
+    ///
+    /// See [`Self::new`].
+    pub fn new_from_system_time(
+        key: impl Into<Vec<u8>>,
+        t0: SystemTime,
+        step: u64,
+        length: u32,
+    ) -> OtpResult<Self> {
+        Totp::new(key, instant_from_system_time(t0), step, length)
+    }
+
+    /// Get a TOTP generator counting steps from a [`SystemTime`], with a
+    /// custom function providing the "now" value as a [`SystemTime`].
+    ///
+    /// See [`Self::new_from_system_time`].
+    pub fn new_from_system_time_with_now<N: Fn() -> SystemTime>(
+        key: impl Into<Vec<u8>>,
+        t0: SystemTime,
+        step: u64,
+        length: u32,
+        now: N,
+    ) -> OtpResult<Totp<impl Fn() -> Instant>> {
+        let now = move || instant_from_system_time(now());
+        Totp::new_with_now(key, instant_from_system_time(t0), step, length, now)
+    }
+
+    /// Get a TOTP generator which computes the HMAC using `algorithm`.
+    ///
+    /// See [`Self::new_with_now`].
+    pub fn new_with_algorithm<F: Fn() -> Instant>(
+        key: impl Into<Vec<u8>>,
+        t0: Instant,
+        step: u64,
+        digits: u32,
+        algorithm: Algorithm,
+        now: F,
+    ) -> OtpResult<Totp<F>> {
+        check_digits(digits)?;
+        Ok(Otp {
+            key: Key::secret(algorithm, key.into()),
+            generator: Time::new(t0, step, now)?,
             digits,
+            checksum: false,
+            encoding: Encoding::decimal(),
+            issuer: None,
+            account: None,
+        })
+    }
+
+    /// Get a TOTP generator which delegates computing the HMAC to `mac`,
+    /// so that the secret key never has to be given to this crate.
+    ///
+    /// See [`Self::new_with_now`].
+    pub fn new_with_mac<F: Fn() -> Instant>(
+        mac: MacFn,
+        t0: Instant,
+        step: u64,
+        digits: u32,
+        now: F,
+    ) -> OtpResult<Totp<F>> {
+        check_digits(digits)?;
+        Ok(Otp {
+            key: Key::External(Arc::from(mac)),
+            generator: Time::new(t0, step, now)?,
+            digits,
+            checksum: false,
+            encoding: Encoding::decimal(),
+            issuer: None,
+            account: None,
+        })
+    }
+}
+
+impl<F: Fn() -> Instant> Totp<F> {
+    /// Compute the passcode for the window containing `at`, rather than
+    /// the current time.
+    pub fn get_at(&self, at: Instant) -> OtpResult<u32> {
+        self.code_at_step(self.generator.steps_at(at))
+    }
+
+    /// Compute the passcode for the current window.
+    ///
+    /// This is [`Otp::get`] without needing mutable access, since the
+    /// clock is only read, so that a generator can be shared, e.g.
+    /// behind an `Arc`.
+    pub fn current(&self) -> OtpResult<u32> {
+        self.code_at_step(self.current_step())
+    }
+
+    /// The passcode for the current window, with leading zeroes and
+    /// `sep` inserted between each group of `group_size` symbols, e.g.
+    /// `"287 082"`, for users to read more easily.
+    ///
+    /// Groups are counted from the left, so the last may be shorter,
+    /// e.g. `"942 870 82"`. A `group_size` of zero leaves the passcode
+    /// ungrouped.
+    pub fn get_grouped(&self, group_size: usize, sep: char) -> OtpResult<String> {
+        let code = self.encoding.format(self.current()?, self.width());
+        if group_size == 0 {
+            return Ok(code);
+        }
+        let mut grouped = String::with_capacity(code.len() * 2);
+        for (i, symbol) in code.chars().enumerate() {
+            if i > 0 && i % group_size == 0 {
+                grouped.push(sep);
+            }
+            grouped.push(symbol);
+        }
+        Ok(grouped)
+    }
+
+    /// Compute the passcode for the window after the current one, e.g.
+    /// to show alongside the current passcode as it nears expiry.
+    pub fn next_code(&self) -> OtpResult<u32> {
+        self.code_at_step(self.current_step().saturating_add(1))
+    }
+
+    /// Compute the passcode for the window before the current one, e.g.
+    /// for an audit log.
+    ///
+    /// Fails during the first window after `t0`, which has no previous
+    /// window.
+    pub fn previous_code(&self) -> OtpResult<u32> {
+        let step = self.current_step().checked_sub(1);
+        self.code_at_step(step.ok_or(HotpError::NoPreviousWindow)?)
+    }
+
+    /// Compute the passcodes for the current window, the `back` windows
+    /// before it and the `forward` windows after it, ordered from the
+    /// earliest window.
+    ///
+    /// These are the codes [`Self::verify_with_skew_range`] would
+    /// accept, e.g. for logging while investigating clock drift. Windows
+    /// before `t0` are left out.
+    pub fn codes_in_range(&self, back: u64, forward: u64) -> OtpResult<Vec<u32>> {
+        let current = self.current_step();
+        (current.saturating_sub(back)..=current.saturating_add(forward))
+            .map(|step| self.code_at_step(step))
+            .collect()
+    }
+
+    /// Check a submitted `code` against the passcode for the current
+    /// window.
+    ///
+    /// The comparison is constant-time, so the time taken does not
+    /// reveal how much of `code` was correct. Returns `false` if the
+    /// passcode cannot be computed.
+    pub fn verify(&self, code: u32) -> bool {
+        self.verify_at_step(code, self.current_step())
+    }
+
+    /// Check a submitted `code` against the passcodes for the current
+    /// window and the `skew` windows either side of it, to allow for
+    /// clock drift between client and server.
+    ///
+    /// A `skew` of zero is equivalent to [`Self::verify`]. As with
+    /// [`Self::verify`], each comparison is constant-time.
+    pub fn verify_with_skew(&self, code: u32, skew: u64) -> bool {
+        self.verify_with_skew_detail(code, skew).is_some()
+    }
+
+    /// Like [`Self::verify_with_skew`], but returns the offset in steps
+    /// from the current window at which `code` matched, e.g. `-1` for
+    /// the previous window, or `None` if it did not match.
+    ///
+    /// Offsets that are consistently non-zero for a device suggest its
+    /// clock has drifted.
+    pub fn verify_with_skew_detail(&self, code: u32, skew: u64) -> Option<i64> {
+        self.verify_around_step(code, skew, skew, self.current_step())
+    }
+
+    /// Check a submitted `code` against the passcodes for the current
+    /// window, the `back` windows before it and the `forward` windows
+    /// after it.
+    ///
+    /// Clock drift is often in one direction, e.g. from a phone whose
+    /// clock runs slow, so a tighter window on the other side narrows
+    /// the time a code can be replayed for. Windows before `t0` are not
+    /// checked. Each comparison is constant-time.
+    pub fn verify_with_skew_range(&self, code: u32, back: u64, forward: u64) -> bool {
+        self.verify_around_step(code, back, forward, self.current_step())
+            .is_some()
+    }
+
+    /// Check several submitted `codes` against the passcodes for the
+    /// current window and the `skew` windows either side of it, returning
+    /// whether any of them is valid.
+    ///
+    /// Every code is compared with every window, so that the time taken
+    /// does not reveal which code matched, nor how many were submitted
+    /// before it.
+    pub fn verify_any(&self, codes: &[u32], skew: u64) -> bool {
+        let current = self.current_step();
+        let first = current.saturating_sub(skew);
+        let last = current.saturating_add(skew);
+        let mut matched = Choice::from(0);
+        for expected in (first..=last).filter_map(|step| self.code_at_step(step).ok()) {
+            for code in codes {
+                matched |= expected.to_be_bytes().ct_eq(&code.to_be_bytes());
+            }
+        }
+        matched.into()
+    }
+
+    /// Check `code` against the `back` steps before `current` and the
+    /// `forward` steps after it, returning the offset of the step that
+    /// matched.
+    pub(crate) fn verify_around_step(
+        &self,
+        code: u32,
+        back: u64,
+        forward: u64,
+        current: u64,
+    ) -> Option<i64> {
+        let first = current.saturating_sub(back);
+        let last = current.saturating_add(forward);
+        self.find_step(code, first, last)
+            .map(|step| step.wrapping_sub(current) as i64)
+    }
+
+    /// Like [`Self::verify_with_skew`], but refuses to accept a code from
+    /// the same or an earlier step than one it has already accepted.
+    ///
+    /// This protects against replay of a code observed by an attacker,
+    /// which would otherwise remain valid for the rest of its window.
+    pub fn verify_once(&mut self, code: u32, skew: u64) -> bool {
+        let current = self.current_step();
+        let first = match self.generator.last_accepted {
+            Some(last) => current.saturating_sub(skew).max(last.saturating_add(1)),
+            None => current.saturating_sub(skew),
+        };
+        let last = current.saturating_add(skew);
+        match self.find_step(code, first, last) {
+            Some(step) => {
+                self.generator.last_accepted = Some(step);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check a submitted code, as typed by the user, against the
+    /// passcode for the current window.
+    ///
+    /// Unlike [`Self::verify`] this keeps any leading zeroes, so
+    /// `"12345"` is not accepted for the passcode `"012345"`. Inputs
+    /// whose length differs from the number of digits are rejected. The
+    /// comparison is constant-time.
+    pub fn verify_str(&self, input: &str) -> bool {
+        if input.chars().count() != self.width() as usize {
+            return false;
         }
+        self.code_at_step(self.current_step())
+            .map(|expected| {
+                let expected = self.encoding.format(expected, self.width());
+                constant_time_eq(expected.as_bytes(), input.as_bytes())
+            })
+            .unwrap_or(false)
+    }
+
+    /// Check a submitted code, as typed by the user, against the
+    /// passcodes for the current window and the `skew` windows either
+    /// side of it, as by [`Self::verify_with_skew`].
+    ///
+    /// As with [`Self::verify_str`], leading zeroes must not be dropped,
+    /// and only decimal passcodes are accepted.
+    pub fn verify_str_with_skew(&self, input: &str, skew: u64) -> bool {
+        self.verify_str_with_skew_detail(input, skew).is_some()
+    }
+
+    /// Like [`Self::verify_str_with_skew`], but returns the offset at
+    /// which `input` matched, as by [`Self::verify_with_skew_detail`].
+    pub fn verify_str_with_skew_detail(&self, input: &str, skew: u64) -> Option<i64> {
+        let code = self.parse_code(input)?;
+        self.verify_with_skew_detail(code, skew)
+    }
+
+    /// The index of the current window, i.e. the number of whole steps
+    /// since `t0`. This is the value the passcode is computed from.
+    pub fn current_step(&self) -> u64 {
+        self.generator.steps()
+    }
+
+    /// The length of each window, in seconds.
+    pub fn step(&self) -> u64 {
+        self.generator.step
+    }
+
+    /// The instant from which windows are counted, usually the Unix
+    /// epoch.
+    pub fn t0(&self) -> Instant {
+        self.generator.t0
+    }
+
+    /// The number of seconds until the current passcode expires.
+    ///
+    /// Exactly at a window boundary this is the full step, since a new
+    /// window has just begun.
+    pub fn remaining_seconds(&self) -> u64 {
+        self.generator.step - self.generator.elapsed().as_secs() % self.generator.step
+    }
+
+    /// The time until the current passcode expires, as by
+    /// [`Self::remaining_seconds`], e.g. for a timer.
+    pub fn time_until_expiry(&self) -> Duration {
+        Duration::from_secs(self.remaining_seconds())
+    }
+
+    /// How far through the current window we are, from `0.0` at its
+    /// start towards `1.0` just before the next window begins.
+    pub fn progress(&self) -> f64 {
+        let step = self.generator.step as f64;
+        self.generator.elapsed().as_secs_f64() % step / step
+    }
+
+    /// The instant at which the current passcode expires, i.e. the start
+    /// of the next window.
+    ///
+    /// If that is beyond the last instant an [`Instant`] can hold, e.g.
+    /// for a huge `t0` or step, the last instant is returned instead.
+    pub fn valid_until(&self) -> Instant {
+        let next = self.current_step().saturating_add(1);
+        let offset = Duration::from_secs(next.saturating_mul(self.generator.step));
+        self.generator
+            .t0
+            .checked_add(offset)
+            .unwrap_or(Instant::at(u64::MAX, 999_999_999))
+    }
+
+    /// Write the `otpauth://totp/` provisioning URI for this generator,
+    /// which authenticator apps can scan as a QR code to enroll it.
+    ///
+    /// The URI format cannot express a `t0` other than the Unix epoch.
+    /// Fails if the HMAC is computed externally, since the secret is then
+    /// unknown, or if the passcodes aren't decimal, e.g. for
+    /// [`Self::new_steam`], since apps would enroll a decimal generator.
+    pub fn to_uri(&self, issuer: &str, account: &str) -> OtpResult<String> {
+        let mut builder = self.uri_builder("totp", issuer, account)?;
+        builder.param("period", &self.generator.step.to_string());
+        Ok(builder.build())
+    }
+
+    /// Write the provisioning URI as by [`Self::to_uri`], labelled with
+    /// the generator's own issuer and account, e.g. those read by
+    /// [`Self::from_uri`].
+    pub fn to_uri_with_metadata(&self) -> OtpResult<String> {
+        self.to_uri(
+            self.issuer().unwrap_or_default(),
+            self.account().unwrap_or_default(),
+        )
+    }
+
+    fn verify_at_step(&self, code: u32, step: u64) -> bool {
+        self.code_at_step(step)
+            .map(|expected| codes_equal(expected, code))
+            .unwrap_or(false)
+    }
+
+    /// The first step from `first` to `last` whose passcode is `code`,
+    /// comparing every step so as not to reveal which matched.
+    fn find_step(&self, code: u32, first: u64, last: u64) -> Option<u64> {
+        find_match(
+            code,
+            (first..=last).map(|step| (step, self.code_at_step(step))),
+        )
+    }
+
+    fn code_at_step(&self, step: u64) -> OtpResult<u32> {
+        self.compute(&step.to_be_bytes())
+    }
+}
+
+/// Parses an `otpauth://totp/` provisioning URI, see [`Totp::from_uri`].
+impl FromStr for Totp {
+    type Err = HotpError;
+
+    fn from_str(s: &str) -> OtpResult<Self> {
+        Totp::from_uri(s)
     }
 }
 
 /// The backing type which implements the [`ToBytes`] interface,
 /// using the current time to generate the value bytes.
-pub struct Time {
-    t0: Instant,
-    step: u64,
-    now: Box<dyn Fn() -> Instant>,
+#[derive(Clone)]
+pub struct Time<F = fn() -> Instant> {
+    pub(crate) t0: Instant,
+    pub(crate) step: u64,
+    now: F,
+    /// The highest step accepted by [`Totp::verify_once`].
+    last_accepted: Option<u64>,
+}
+
+impl<F> fmt::Debug for Time<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Time")
+            .field("t0", &self.t0)
+            .field("step", &self.step)
+            .field("last_accepted", &self.last_accepted)
+            .finish_non_exhaustive()
+    }
 }
 
-impl ToBytes for Time {
+impl<F: Fn() -> Instant> Time<F> {
+    pub(crate) fn new(t0: Instant, step: u64, now: F) -> OtpResult<Self> {
+        if step == 0 {
+            return Err(HotpError::InvalidStep);
+        }
+        Ok(Time {
+            t0,
+            step,
+            now,
+            last_accepted: None,
+        })
+    }
+
+    /// The time between `t0` and now.
+    fn elapsed(&self) -> Duration {
+        self.elapsed_at((self.now)())
+    }
+
+    /// The time between `t0` and `at`.
+    ///
+    /// An `at` before `t0` is treated as `t0` itself, so such times fall
+    /// in the first window rather than panicking.
+    fn elapsed_at(&self, at: Instant) -> Duration {
+        at.saturating_duration_since(self.t0)
+    }
+
+    /// The number of whole steps between `t0` and now.
+    fn steps(&self) -> u64 {
+        self.steps_at((self.now)())
+    }
+
+    /// The number of whole steps between `t0` and `at`.
+    pub(crate) fn steps_at(&self, at: Instant) -> u64 {
+        self.elapsed_at(at).as_secs() / self.step
+    }
+}
+
+impl<F: Fn() -> Instant> ToBytes for Time<F> {
+    type Bytes = [u8; 8];
+
     fn to_bytes(&mut self) -> OtpResult<[u8; 8]> {
-        let t0 = self.t0;
-        let now = (self.now)();
-        let elapsed = now - t0;
-        let steps = elapsed.as_secs() / self.step;
-        Ok(steps.to_be_bytes().into())
+        Ok(self.steps().to_be_bytes())
+    }
+}
+
+/// Displays the current passcode, zero-padded to the configured number
+/// of digits.
+impl<F: Fn() -> Instant> fmt::Display for Totp<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_code(&self.current_step().to_be_bytes(), f)
+    }
+}
+
+/// Convert a [`SystemTime`] by its duration since the Unix epoch,
+/// clamping earlier times to the epoch.
+pub(crate) fn instant_from_system_time(time: SystemTime) -> Instant {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Instant::at(since_epoch.as_secs(), since_epoch.subsec_nanos())
+}
+
+/// A TOTP step in seconds, which must be whole and at least one.
+fn whole_seconds(step: Duration) -> OtpResult<u64> {
+    if step.subsec_nanos() != 0 {
+        return Err(HotpError::InvalidStep);
+    }
+    Ok(step.as_secs())
+}
+
+/// Builds a [`Totp`] generator one option at a time, as an alternative
+/// to the positional constructors.
+///
+/// Only the secret is required. Otherwise the defaults are those of
+/// most authenticator apps: 6 digit SHA-1 passcodes in 30 second steps
+/// from the Unix epoch.
+///
+/// ```
+/// use otp2::{Algorithm, TotpBuilder};
+///
+/// let totp = TotpBuilder::new()
+///     .secret("12345678901234567890")
+///     .algorithm(Algorithm::Sha256)
+///     .digits(8)
+///     .build()
+///     .unwrap();
+/// ```
+pub struct TotpBuilder<F = fn() -> Instant> {
+    secret: Option<Zeroizing<Vec<u8>>>,
+    t0: Instant,
+    step: Duration,
+    digits: u32,
+    algorithm: Algorithm,
+    now: F,
+    issuer: Option<String>,
+    account: Option<String>,
+}
+
+impl Default for TotpBuilder {
+    fn default() -> Self {
+        TotpBuilder {
+            secret: None,
+            t0: Instant::at(0, 0),
+            step: Duration::from_secs(30),
+            digits: 6,
+            algorithm: Algorithm::Sha1,
+            now: SYSTEM_NOW,
+            issuer: None,
+            account: None,
+        }
+    }
+}
+
+impl TotpBuilder {
+    /// Start building a generator with the default options.
+    pub fn new() -> Self {
+        TotpBuilder::default()
+    }
+}
+
+impl<F: Fn() -> Instant> TotpBuilder<F> {
+    /// The shared secret, as raw bytes.
+    pub fn secret(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.secret = Some(Zeroizing::new(key.into()));
+        self
+    }
+
+    /// The time from which steps are counted.
+    pub fn t0(mut self, t0: Instant) -> Self {
+        self.t0 = t0;
+        self
+    }
+
+    /// The length of each window, in seconds.
+    pub fn step(mut self, step: u64) -> Self {
+        self.step = Duration::from_secs(step);
+        self
+    }
+
+    /// The length of each window, which must be a whole number of
+    /// seconds.
+    pub fn step_duration(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// The number of digits in each passcode.
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.digits = digits;
+        self
+    }
+
+    /// The hash algorithm used to compute the HMAC.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// A custom function to provide the "now" value.
+    pub fn now<N: Fn() -> Instant>(self, now: N) -> TotpBuilder<N> {
+        TotpBuilder {
+            secret: self.secret,
+            t0: self.t0,
+            step: self.step,
+            digits: self.digits,
+            algorithm: self.algorithm,
+            now,
+            issuer: self.issuer,
+            account: self.account,
+        }
+    }
+
+    /// See [`Totp::with_issuer`](crate::Otp::with_issuer).
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// See [`Totp::with_account`](crate::Otp::with_account).
+    pub fn account(mut self, account: impl Into<String>) -> Self {
+        self.account = Some(account.into());
+        self
+    }
+
+    /// Build the generator.
+    ///
+    /// Fails if no secret was given, or as [`Totp::new_with_duration`]
+    /// does for an invalid number of digits or step.
+    pub fn build(self) -> OtpResult<Totp<F>> {
+        let mut secret = self.secret.ok_or(HotpError::MissingSecret)?;
+        let mut totp = Totp::new_with_algorithm(
+            std::mem::take(&mut *secret),
+            self.t0,
+            whole_seconds(self.step)?,
+            self.digits,
+            self.algorithm,
+            self.now,
+        )?;
+        totp.issuer = self.issuer;
+        totp.account = self.account;
+        Ok(totp)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::{
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
     use test_case::test_case;
     use unix_time::Instant;
 
-    use crate::Totp;
+    use crate::{Algorithm, HotpError, NowFn, Totp, TotpBuilder};
 
-    // These test cases are copied from RFC 6238
+    // These test cases are copied from RFC 6238. Each algorithm uses a
+    // seed of the digest's length.
     // https://datatracker.ietf.org/doc/html/rfc6238#appendix-B
+    #[allow(clippy::zero_prefixed_literal)]
     #[test_case(59, 94287082)]
     #[test_case(1111111109, 07081804)]
     #[test_case(1111111111, 14050471)]
@@ -74,14 +812,685 @@ mod test {
         let key = "12345678901234567890".to_string();
         let step = 30;
         let t0 = Instant::at(0, 0);
-        let mut otp = Totp::new_with_now(
-            key,
-            t0,
-            step,
-            digits,
-            Box::new(move || Instant::at(count, 0)),
-        );
+        let mut otp =
+            Totp::new_with_now(key, t0, step, digits, move || Instant::at(count, 0)).unwrap();
+        let actual_code = otp.get().unwrap();
+        assert_eq!(actual_code, expected_code);
+    }
+
+    #[test_case(59, 46119246)]
+    #[test_case(1111111109, 68084774)]
+    #[test_case(1111111111, 67062674)]
+    #[test_case(1234567890, 91819424)]
+    #[test_case(2000000000, 90698825)]
+    #[test_case(20000000000, 77737706)]
+    fn it_computes_correct_sha256_totp_vectors(count: u64, expected_code: u32) {
+        let digits = 8;
+        let key = "12345678901234567890123456789012".to_string();
+        let step = 30;
+        let t0 = Instant::at(0, 0);
+        let mut otp =
+            Totp::new_with_algorithm(key, t0, step, digits, Algorithm::Sha256, move || {
+                Instant::at(count, 0)
+            })
+            .unwrap();
         let actual_code = otp.get().unwrap();
         assert_eq!(actual_code, expected_code);
     }
+
+    #[test_case(59, 90693936)]
+    #[test_case(1111111109, 25091201)]
+    #[test_case(1111111111, 99943326)]
+    #[test_case(1234567890, 93441116)]
+    #[test_case(2000000000, 38618901)]
+    #[test_case(20000000000, 47863826)]
+    fn it_computes_correct_sha512_totp_vectors(count: u64, expected_code: u32) {
+        let digits = 8;
+        let key = "1234567890123456789012345678901234567890123456789012345678901234".to_string();
+        let step = 30;
+        let t0 = Instant::at(0, 0);
+        let mut otp =
+            Totp::new_with_algorithm(key, t0, step, digits, Algorithm::Sha512, move || {
+                Instant::at(count, 0)
+            })
+            .unwrap();
+        let actual_code = otp.get().unwrap();
+        assert_eq!(actual_code, expected_code);
+    }
+
+    #[test]
+    fn it_defaults_to_sha1() {
+        let key = "12345678901234567890".to_string();
+        let t0 = Instant::at(0, 0);
+        let now = Instant::at(1111111111, 0);
+        let mut default = Totp::new_with_now(key.clone(), t0, 30, 8, move || now).unwrap();
+        let mut sha1 =
+            Totp::new_with_algorithm(key, t0, 30, 8, Algorithm::Sha1, move || now).unwrap();
+        assert_eq!(default.get().unwrap(), 14050471);
+        assert_eq!(sha1.get().unwrap(), 14050471);
+    }
+
+    // Put "now" 45 seconds after t0, i.e. in the middle of step 1, which
+    // is the step used by the T = 59 vectors of RFC 6238.
+    fn t0_in_step_one() -> Instant {
+        Instant::now() - Duration::from_secs(45)
+    }
+
+    #[test]
+    fn it_computes_correct_sha256_totp() {
+        let key = "12345678901234567890123456789012".to_string();
+        let mut otp = Totp::new_sha256(key, t0_in_step_one(), 30, 8).unwrap();
+        assert_eq!(otp.get().unwrap(), 46119246);
+    }
+
+    #[test]
+    fn it_computes_correct_sha512_totp() {
+        let key = "1234567890123456789012345678901234567890123456789012345678901234".to_string();
+        let mut otp = Totp::new_sha512(key, t0_in_step_one(), 30, 8).unwrap();
+        assert_eq!(otp.get().unwrap(), 90693936);
+    }
+
+    #[test]
+    fn it_decodes_a_base32_secret() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let mut otp = Totp::from_base32(secret, t0_in_step_one(), 30, 8).unwrap();
+        assert_eq!(otp.get().unwrap(), 94287082);
+    }
+
+    #[test]
+    fn it_decodes_a_hex_secret() {
+        let secret = "3132333435363738393031323334353637383930";
+        let mut otp = Totp::from_hex(secret, t0_in_step_one(), 30, 8).unwrap();
+        assert_eq!(otp.get().unwrap(), 94287082);
+    }
+
+    #[test]
+    fn it_rejects_weak_keys_when_strict() {
+        let result = Totp::new_strict("1", Instant::at(0, 0), 30, 6);
+        assert!(matches!(result, Err(HotpError::WeakKey { length: 1 })));
+        assert!(Totp::new_strict("12345678901234567890", Instant::at(0, 0), 30, 6).is_ok());
+    }
+
+    #[test]
+    fn it_redacts_the_key_when_debug_formatted() {
+        let key = "12345678901234567890123456789012";
+        let totp = Totp::new_sha256(key, Instant::at(0, 0), 30, 8).unwrap();
+        let formatted = format!("{totp:?}");
+        assert!(formatted.contains("<redacted>"));
+        assert!(formatted.contains("digits: 8"));
+        assert!(formatted.contains("Sha256"));
+        assert!(!formatted.contains(key));
+        assert!(!formatted.contains("49, 50, 51"));
+    }
+
+    #[test]
+    fn it_keeps_leading_zeroes_in_string_codes() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(1111111109, 0);
+        let mut otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert_eq!(otp.get_string().unwrap(), "07081804");
+    }
+
+    #[test]
+    fn it_displays_the_current_code() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(1111111109, 0);
+        let mut otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert_eq!(otp.to_string(), "07081804");
+        assert_eq!(otp.to_string(), otp.get_string().unwrap());
+    }
+
+    #[test_case(0)]
+    #[test_case(11)]
+    fn it_rejects_invalid_digits(digits: u32) {
+        let result = Totp::new("12345678901234567890", Instant::at(0, 0), 30, digits);
+        assert!(matches!(result, Err(HotpError::InvalidDigits { digits: d }) if d == digits));
+    }
+
+    #[test]
+    fn it_accepts_valid_digits() {
+        assert!(Totp::new("12345678901234567890", Instant::at(0, 0), 30, 6).is_ok());
+    }
+
+    // This test case is copied from steamguard-cli
+    // https://github.com/dyc3/steamguard-cli
+    #[test]
+    fn it_computes_correct_steam_code() {
+        // The shared secret "zvIayp3JPvtvX/QGHqsqKBk/44s=" in hex
+        let key = hex_literal::hex!("cef21aca9dc93efb6f5ff4061eab2a28193fe38b");
+        let now = || Instant::at(1616374841, 0);
+        let mut otp = Totp::new_steam_with_now(key, now).unwrap();
+        assert_eq!(otp.get_string().unwrap(), "2F9J5");
+        assert_eq!(otp.to_string(), "2F9J5");
+        assert_eq!(otp.get_into(&mut [0; 5]).unwrap(), "2F9J5");
+    }
+
+    #[test_case(6, 3, ' ' => "287 082" ; "six digits in threes")]
+    #[test_case(8, 4, ' ' => "9428 7082" ; "eight digits in fours")]
+    #[test_case(8, 3, '-' => "942-870-82" ; "uneven groups")]
+    #[test_case(6, 0, ' ' => "287082" ; "ungrouped")]
+    #[test_case(6, 6, ' ' => "287082" ; "one group")]
+    fn it_groups_the_current_code(digits: u32, group_size: usize, sep: char) -> String {
+        let now = || Instant::at(59, 0);
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, digits, now).unwrap();
+        otp.get_grouped(group_size, sep).unwrap()
+    }
+
+    #[test]
+    fn it_computes_the_current_code_through_a_shared_reference() {
+        let now = || Instant::at(59, 0);
+        let mut otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now).unwrap();
+        assert_eq!(otp.current().unwrap(), otp.get().unwrap());
+
+        let otp = Arc::new(otp);
+        let shared = Arc::clone(&otp);
+        let code = std::thread::spawn(move || shared.current().unwrap());
+        assert_eq!(code.join().unwrap(), 94287082);
+    }
+
+    #[test]
+    fn it_previews_the_next_code() {
+        let key = "12345678901234567890";
+        let before = || Instant::at(59, 0);
+        let after = || Instant::at(60, 0);
+        let before = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, before).unwrap();
+        let mut after = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, after).unwrap();
+        assert_eq!(before.next_code().unwrap(), after.get().unwrap());
+        assert_eq!(
+            before.next_code().unwrap(),
+            before.get_at(Instant::at(60, 0)).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_computes_the_previous_code() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(60, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert_eq!(otp.previous_code().unwrap(), 94287082);
+        assert_eq!(
+            otp.previous_code().unwrap(),
+            otp.get_at(Instant::at(30, 0)).unwrap()
+        );
+
+        let now = || Instant::at(29, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert!(matches!(
+            otp.previous_code(),
+            Err(HotpError::NoPreviousWindow)
+        ));
+    }
+
+    #[test]
+    fn it_lists_the_codes_in_a_range() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(1111111109, 0);
+        let mut otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        let codes = otp.codes_in_range(2, 2).unwrap();
+        assert_eq!(codes.len(), 5);
+        assert_eq!(codes[2], otp.get().unwrap());
+        assert_eq!(codes[1], otp.previous_code().unwrap());
+        assert_eq!(codes[3], otp.next_code().unwrap());
+        assert_eq!(otp.codes_in_range(0, 1).unwrap(), codes[2..4]);
+    }
+
+    #[test]
+    fn it_leaves_out_codes_before_t0() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(59, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        let codes = otp.codes_in_range(5, 0).unwrap();
+        assert_eq!(codes.len(), 2);
+        assert_eq!(codes[1], 94287082);
+    }
+
+    #[test]
+    fn it_verifies_the_current_code() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(59, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert!(otp.verify(94287082));
+        assert!(!otp.verify(94287083));
+        // The code for a much later window
+        assert!(!otp.verify(7081804));
+    }
+
+    #[test]
+    fn it_verifies_codes_within_the_skew() {
+        let key = "12345678901234567890";
+        // Step 2, one after the step of the T = 59 vector
+        let now = || Instant::at(65, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert!(!otp.verify_with_skew(94287082, 0));
+        assert!(otp.verify_with_skew(94287082, 1));
+    }
+
+    // Step 2, one after the step of the T = 59 vector
+    #[test_case(&[94287082], 1 => true ; "valid")]
+    #[test_case(&[12345678, 94287082], 1 => true ; "valid after invalid")]
+    #[test_case(&[94287082, 12345678], 1 => true ; "valid before invalid")]
+    #[test_case(&[12345678, 87654321], 1 => false ; "all invalid")]
+    #[test_case(&[12345678, 94287082], 0 => false ; "valid outside the skew")]
+    #[test_case(&[], 1 => false ; "none")]
+    fn it_verifies_any_of_several_codes(codes: &[u32], skew: u64) -> bool {
+        let key = "12345678901234567890";
+        let now = || Instant::at(65, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        otp.verify_any(codes, skew)
+    }
+
+    #[test]
+    fn it_does_not_underflow_when_the_skew_exceeds_the_step() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(59, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert!(otp.verify_with_skew(94287082, 5));
+    }
+
+    // T = 59 gives the passcode "94287082", in step 1
+    #[test_case("94287082" => Some(0) ; "current")]
+    #[test_case("84755224" => Some(-1) ; "previous")]
+    #[test_case("4755224" => None ; "missing leading digit")]
+    #[test_case("+4755224" => None ; "sign")]
+    #[test_case("9428708 " => None ; "space")]
+    #[test_case("94287083" => None ; "wrong code")]
+    fn it_verifies_typed_codes_with_skew(input: &str) -> Option<i64> {
+        let key = "12345678901234567890";
+        let now = || Instant::at(59, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert_eq!(
+            otp.verify_str_with_skew(input, 1),
+            otp.verify_str_with_skew_detail(input, 1).is_some()
+        );
+        otp.verify_str_with_skew_detail(input, 1)
+    }
+
+    // The T = 59 vector is in step 1, so an earlier clock needs forward
+    // skew and a later one backward skew.
+    #[test_case(5, 0, 1 => true ; "forward")]
+    #[test_case(5, 2, 0 => false ; "forward without forward skew")]
+    #[test_case(95, 2, 0 => true ; "back")]
+    #[test_case(95, 1, 5 => false ; "beyond the backward skew")]
+    #[test_case(35, 0, 0 => true ; "current")]
+    #[test_case(5, u64::MAX, 0 => false ; "backward skew past t0")]
+    fn it_verifies_codes_within_an_asymmetric_skew(now: u64, back: u64, forward: u64) -> bool {
+        let key = "12345678901234567890";
+        let now = move || Instant::at(now, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        otp.verify_with_skew_range(94287082, back, forward)
+    }
+
+    // T = 1111111109 gives the passcode "07081804"
+    #[test_case("07081804" => true ; "exact")]
+    #[test_case("7081804" => false ; "missing leading zero")]
+    #[test_case("007081804" => false ; "extra leading zero")]
+    #[test_case("07081805" => false ; "wrong code")]
+    #[test_case("" => false ; "empty")]
+    fn it_verifies_typed_codes(input: &str) -> bool {
+        let key = "12345678901234567890";
+        let now = || Instant::at(1111111109, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        otp.verify_str(input)
+    }
+
+    // The T = 59 vector is in step 1
+    #[test_case(35 => Some(0) ; "current")]
+    #[test_case(65 => Some(-1) ; "previous")]
+    #[test_case(5 => Some(1) ; "next")]
+    #[test_case(95 => None ; "outside the skew")]
+    fn it_reports_the_matching_offset(now: u64) -> Option<i64> {
+        let key = "12345678901234567890";
+        let now = move || Instant::at(now, 0);
+        let otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        otp.verify_with_skew_detail(94287082, 1)
+    }
+
+    #[test]
+    fn it_rejects_replayed_codes() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(59, 0);
+        let mut otp = Totp::new_with_now(key, Instant::at(0, 0), 30, 8, now).unwrap();
+        assert!(!otp.verify_once(94287083, 1));
+        assert!(otp.verify_once(94287082, 1));
+        assert!(!otp.verify_once(94287082, 1));
+        // The code for the previous window is now also too old
+        assert!(otp.verify_with_skew(84755224, 1));
+        assert!(!otp.verify_once(84755224, 1));
+    }
+
+    #[test_case(0 => 30 ; "at the boundary")]
+    #[test_case(1 => 29)]
+    #[test_case(29 => 1)]
+    #[test_case(59 => 1 ; "end of the second window")]
+    #[test_case(60 => 30 ; "start of the third window")]
+    fn it_computes_the_remaining_seconds(now: u64) -> u64 {
+        let now = move || Instant::at(now, 0);
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now).unwrap();
+        otp.remaining_seconds()
+    }
+
+    #[test_case(0)]
+    #[test_case(1)]
+    #[test_case(29)]
+    #[test_case(30)]
+    #[test_case(1111111109)]
+    fn it_agrees_on_the_time_until_expiry(now: u64) {
+        let now = move || Instant::at(now, 0);
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now).unwrap();
+        assert_eq!(otp.time_until_expiry().as_secs(), otp.remaining_seconds());
+        assert_eq!(otp.time_until_expiry().subsec_nanos(), 0);
+    }
+
+    #[test]
+    fn it_reads_back_the_configuration() {
+        let otp = Totp::new("12345678901234567890", Instant::at(100, 0), 60, 8).unwrap();
+        assert_eq!(otp.digits(), 8);
+        assert_eq!(otp.step(), 60);
+        assert_eq!(otp.t0(), Instant::at(100, 0));
+    }
+
+    #[test_case(0 => 0)]
+    #[test_case(29 => 0)]
+    #[test_case(30 => 1)]
+    #[test_case(1111111109 => 37037036)]
+    fn it_computes_the_current_step(now: u64) -> u64 {
+        let now = move || Instant::at(now, 0);
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now).unwrap();
+        otp.current_step()
+    }
+
+    #[test_case(0 => 30)]
+    #[test_case(29 => 30)]
+    #[test_case(30 => 60)]
+    #[test_case(1111111109 => 1111111110)]
+    fn it_computes_when_the_code_expires(now: u64) -> u64 {
+        let clock = move || Instant::at(now, 0);
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, clock).unwrap();
+        let valid_until = otp.valid_until();
+        assert_eq!(valid_until.secs() - now, otp.remaining_seconds());
+        valid_until.secs()
+    }
+
+    #[test_case(u64::MAX - 10, 30 ; "late t0")]
+    #[test_case(1, u64::MAX ; "long step")]
+    fn it_saturates_when_the_code_expires(t0: u64, step: u64) {
+        let clock = move || Instant::at(t0, 0);
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(t0, 0), step, 8, clock).unwrap();
+        assert_eq!(otp.valid_until(), Instant::at(u64::MAX, 999_999_999));
+    }
+
+    #[test_case(0, 0 => 0.0 ; "start of the first window")]
+    #[test_case(15, 0 => 0.5 ; "halfway")]
+    #[test_case(29, 700_000_000 => 0.99 ; "just before the boundary")]
+    #[test_case(30, 0 => 0.0 ; "start of the second window")]
+    fn it_computes_the_progress_through_the_window(secs: u64, nanos: u32) -> f64 {
+        let now = move || Instant::at(secs, nanos);
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now).unwrap();
+        (otp.progress() * 100.0).round() / 100.0
+    }
+
+    #[test_case(59 => 94287082)]
+    #[test_case(1111111109 => 7081804)]
+    #[test_case(1234567890 => 89005924)]
+    fn it_computes_the_code_at_an_instant(at: u64) -> u32 {
+        // The clock is never consulted
+        let now = || unreachable!();
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now).unwrap();
+        otp.get_at(Instant::at(at, 0)).unwrap()
+    }
+
+    #[test]
+    fn it_treats_times_before_t0_as_the_first_window() {
+        let key = "12345678901234567890";
+        let now = || Instant::at(100, 0);
+        let mut otp = Totp::new_with_now(key, Instant::at(1000, 0), 30, 8, now).unwrap();
+        assert_eq!(otp.current_step(), 0);
+        assert_eq!(
+            otp.get().unwrap(),
+            otp.get_at(Instant::at(1000, 0)).unwrap()
+        );
+        assert_eq!(otp.remaining_seconds(), 30);
+    }
+
+    #[test]
+    fn it_rejects_a_zero_step() {
+        let result = Totp::new("12345678901234567890", Instant::at(0, 0), 0, 8);
+        assert!(matches!(result, Err(HotpError::InvalidStep)));
+    }
+
+    #[test]
+    fn it_accepts_the_step_as_a_duration() {
+        let step = Duration::from_secs(60);
+        let otp =
+            Totp::new_with_duration("12345678901234567890", Instant::at(0, 0), step, 8).unwrap();
+        assert_eq!(otp.generator.step, 60);
+        let otp = TotpBuilder::new()
+            .secret("12345678901234567890")
+            .step_duration(step)
+            .build()
+            .unwrap();
+        assert_eq!(otp.generator.step, 60);
+    }
+
+    #[test_case(Duration::ZERO ; "zero")]
+    #[test_case(Duration::from_millis(500) ; "sub-second")]
+    #[test_case(Duration::from_millis(1500) ; "fractional")]
+    fn it_rejects_invalid_step_durations(step: Duration) {
+        let result = Totp::new_with_duration("12345678901234567890", Instant::at(0, 0), step, 8);
+        assert!(matches!(result, Err(HotpError::InvalidStep)));
+        let result = TotpBuilder::new()
+            .secret("12345678901234567890")
+            .step_duration(step)
+            .build();
+        assert!(matches!(result, Err(HotpError::InvalidStep)));
+    }
+
+    #[test]
+    fn it_counts_steps_from_a_system_time() {
+        let t0 = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let otp = Totp::new_from_system_time("12345678901234567890", t0, 30, 8).unwrap();
+        assert_eq!(otp.generator.t0, Instant::at(1_000_000_000, 0));
+
+        let now = || UNIX_EPOCH + Duration::from_secs(59);
+        let mut otp =
+            Totp::new_from_system_time_with_now("12345678901234567890", UNIX_EPOCH, 30, 8, now)
+                .unwrap();
+        assert_eq!(otp.get().unwrap(), 94287082);
+    }
+
+    #[test]
+    fn it_clamps_system_times_before_the_epoch() {
+        let t0 = UNIX_EPOCH - Duration::from_secs(60);
+        let otp = Totp::new_from_system_time("12345678901234567890", t0, 30, 8).unwrap();
+        assert_eq!(otp.generator.t0, Instant::at(0, 0));
+    }
+
+    #[test]
+    fn it_writes_a_provisioning_uri() {
+        let otp = Totp::new_sha256("12345678901234567890", Instant::at(0, 0), 60, 8).unwrap();
+        assert_eq!(
+            otp.to_uri("ACME Co", "john.doe@email.com").unwrap(),
+            "otpauth://totp/ACME%20Co:john.doe%40email.com\
+             ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=ACME%20Co\
+             &algorithm=SHA256&digits=8&period=60"
+        );
+    }
+
+    #[test]
+    fn it_cannot_write_a_uri_without_the_secret() {
+        let mac = Box::new(|_: &[u8]| Ok(vec![0; 20]));
+        let now = Instant::now;
+        let otp = Totp::new_with_mac(mac, Instant::at(0, 0), 30, 6, now).unwrap();
+        let result = otp.to_uri("ACME Co", "john.doe@email.com");
+        assert!(matches!(result, Err(HotpError::SecretUnavailable)));
+    }
+
+    #[test]
+    fn it_cannot_write_a_uri_for_steam() {
+        let otp = Totp::new_steam("12345678901234567890").unwrap();
+        let result = otp.to_uri("Steam", "gaben");
+        assert!(matches!(result, Err(HotpError::UnsupportedUri { .. })));
+    }
+
+    #[test]
+    fn it_round_trips_a_provisioning_uri() {
+        let key = "12345678901234567890";
+        let otp = Totp::new_sha512(key, Instant::at(0, 0), 60, 8).unwrap();
+        let uri = otp.to_uri("ACME Co", "john.doe@email.com").unwrap();
+        let parsed = Totp::from_uri(&uri).unwrap();
+        assert_eq!(parsed.to_uri("ACME Co", "john.doe@email.com").unwrap(), uri);
+        let at = Instant::at(1234567890, 0);
+        assert_eq!(parsed.get_at(at).unwrap(), otp.get_at(at).unwrap());
+    }
+
+    #[test]
+    fn it_rejects_a_hotp_uri() {
+        let result = Totp::from_uri("otpauth://hotp/alice?secret=GEZDGNBV&counter=0");
+        assert!(matches!(result, Err(HotpError::InvalidUri { .. })));
+    }
+
+    #[test]
+    fn it_keeps_the_uri_metadata() {
+        let uri = "otpauth://totp/ACME%20Co:john.doe%40email.com\
+                   ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=ACME%20Co\
+                   &algorithm=SHA1&digits=6&period=30";
+        let otp = Totp::from_uri(uri).unwrap();
+        assert_eq!(otp.issuer(), Some("ACME Co"));
+        assert_eq!(otp.account(), Some("john.doe@email.com"));
+        assert_eq!(otp.to_uri_with_metadata().unwrap(), uri);
+    }
+
+    #[test]
+    fn it_labels_a_generator_without_affecting_the_code() {
+        let key = "12345678901234567890";
+        let otp = Totp::new(key, Instant::at(0, 0), 30, 8).unwrap();
+        let labelled = Totp::new(key, Instant::at(0, 0), 30, 8)
+            .unwrap()
+            .with_issuer("ACME Co")
+            .with_account("john.doe@email.com");
+        let at = Instant::at(59, 0);
+        assert_eq!(labelled.get_at(at).unwrap(), otp.get_at(at).unwrap());
+        assert_eq!(otp.issuer(), None);
+        assert_eq!(
+            labelled.to_uri_with_metadata().unwrap(),
+            labelled.to_uri("ACME Co", "john.doe@email.com").unwrap()
+        );
+    }
+
+    #[test]
+    fn it_builds_with_defaults() {
+        let otp = TotpBuilder::new()
+            .secret("12345678901234567890")
+            .build()
+            .unwrap();
+        assert_eq!(otp.get_at(Instant::at(59, 0)).unwrap(), 287082);
+        assert_eq!(otp.to_string().len(), 6);
+    }
+
+    #[test]
+    fn it_builds_with_every_option() {
+        let otp = TotpBuilder::new()
+            .secret("12345678901234567890123456789012")
+            .algorithm(Algorithm::Sha256)
+            .digits(8)
+            .step(30)
+            .t0(Instant::at(0, 0))
+            .now(|| Instant::at(59, 0))
+            .issuer("ACME Co")
+            .account("john.doe@email.com")
+            .build()
+            .unwrap();
+        assert_eq!(otp.to_string(), "46119246");
+        assert_eq!(otp.issuer(), Some("ACME Co"));
+        assert_eq!(otp.account(), Some("john.doe@email.com"));
+    }
+
+    #[test]
+    fn it_validates_the_built_options() {
+        let builder = || TotpBuilder::new().secret("12345678901234567890");
+        let result = builder().digits(0).build();
+        assert!(matches!(
+            result,
+            Err(HotpError::InvalidDigits { digits: 0 })
+        ));
+        let result = builder().step(0).build();
+        assert!(matches!(result, Err(HotpError::InvalidStep)));
+        let result = TotpBuilder::new().build();
+        assert!(matches!(result, Err(HotpError::MissingSecret)));
+    }
+
+    #[test_case("GEZDGNBVGY3TQOJQ", 30, 0 ; "bad digits")]
+    #[test_case("GEZDGNBVGY3TQOJQ", 0, 6 ; "zero step")]
+    #[test_case("GEZDGNBVGY3TQOJQ0", 30, 6 ; "bad secret")]
+    fn it_rejects_misconfiguration_when_constructed(secret: &str, step: u64, digits: u32) {
+        assert!(Totp::from_base32(secret, Instant::at(0, 0), step, digits).is_err());
+    }
+
+    #[test]
+    fn it_parses_from_a_string() {
+        let otp: Totp = "otpauth://totp/ACME%20Co:john.doe%40email.com\
+                         ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=ACME%20Co&digits=8"
+            .parse()
+            .unwrap();
+        assert_eq!(otp.get_at(Instant::at(1111111109, 0)).unwrap(), 7081804);
+        assert!(matches!(
+            "not a uri".parse::<Totp>(),
+            Err(HotpError::InvalidUri { .. })
+        ));
+    }
+
+    #[test]
+    fn it_clones_a_generator() {
+        let now = || Instant::at(59, 0);
+        let otp = Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now)
+            .unwrap()
+            .with_account("john.doe@email.com");
+        let clone = otp.clone();
+        assert_eq!(clone.to_string(), otp.to_string());
+        assert_eq!(clone.to_string(), "94287082");
+        assert_eq!(clone.account(), otp.account());
+    }
+
+    #[test]
+    fn it_verifies_from_several_threads() {
+        let now = || Instant::at(59, 0);
+        let otp =
+            Totp::new_with_now("12345678901234567890", Instant::at(0, 0), 30, 8, now).unwrap();
+        let otp = std::sync::Arc::new(otp);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let otp = std::sync::Arc::clone(&otp);
+                std::thread::spawn(move || otp.verify(94287082))
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn it_stores_generators_with_different_clocks_together() {
+        let key = "12345678901234567890";
+        let t0 = Instant::at(0, 0);
+        // Boxing is what lets the different closures share a type.
+        let clocks: [NowFn; 2] = [
+            Box::new(|| Instant::at(59, 0)),
+            Box::new(|| Instant::at(1111111109, 0)),
+        ];
+        let otps: Vec<Totp<NowFn>> = clocks
+            .into_iter()
+            .map(|now| Totp::new_with_now(key, t0, 30, 8, now).unwrap())
+            .collect();
+        assert_eq!(otps[0].to_string(), "94287082");
+        assert_eq!(otps[1].to_string(), "07081804");
+    }
 }
@@ -0,0 +1,339 @@
+//! `otpauth://` provisioning URIs, which authenticator apps scan to
+//! enroll an account.
+//!
+//! See <https://github.com/google/google-authenticator/wiki/Key-Uri-Format>.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{base32, Algorithm, HotpError, OtpResult};
+
+const SCHEME: &str = "otpauth://";
+
+/// Builds a provisioning URI of the form
+/// `otpauth://KIND/ISSUER:ACCOUNT?PARAMS`.
+pub(crate) struct Builder {
+    uri: String,
+    has_params: bool,
+}
+
+impl Builder {
+    pub(crate) fn new(kind: &str, issuer: &str, account: &str) -> Self {
+        let mut uri = format!("otpauth://{kind}/");
+        if !issuer.is_empty() {
+            uri.push_str(&encode(issuer));
+            uri.push(':');
+        }
+        uri.push_str(&encode(account));
+        Builder {
+            uri,
+            has_params: false,
+        }
+    }
+
+    /// Append a query parameter, skipping it if `value` is empty.
+    pub(crate) fn param(&mut self, name: &str, value: &str) -> &mut Self {
+        if value.is_empty() {
+            return self;
+        }
+        self.uri.push(if self.has_params { '&' } else { '?' });
+        self.uri.push_str(name);
+        self.uri.push('=');
+        self.uri.push_str(&encode(value));
+        self.has_params = true;
+        self
+    }
+
+    pub(crate) fn build(&self) -> String {
+        self.uri.clone()
+    }
+}
+
+/// The settings read from a provisioning URI.
+#[derive(Debug)]
+pub(crate) struct Parsed {
+    pub(crate) issuer: Option<String>,
+    pub(crate) account: String,
+    pub(crate) secret: Vec<u8>,
+    pub(crate) algorithm: Algorithm,
+    pub(crate) digits: u32,
+    // Only TOTP has a period.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) period: u64,
+    pub(crate) counter: Option<u64>,
+}
+
+/// Parse a provisioning URI, which must be of the given `kind`, i.e.
+/// `"totp"` or `"hotp"`.
+///
+/// Unrecognised parameters, such as `image`, are ignored.
+pub(crate) fn parse(uri: &str, kind: &str) -> OtpResult<Parsed> {
+    let rest = uri
+        .get(..SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|_| &uri[SCHEME.len()..])
+        .ok_or_else(|| invalid("expected an otpauth:// URI"))?;
+    let (actual_kind, rest) = rest
+        .split_once('/')
+        .ok_or_else(|| invalid("missing label"))?;
+    if !actual_kind.eq_ignore_ascii_case(kind) {
+        return Err(invalid(format!(
+            "expected a {kind} URI, not {actual_kind:?}"
+        )));
+    }
+    let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (label_issuer, account) = match split_label(label) {
+        (Some(issuer), account) => (Some(decode(issuer)?), decode(account)?),
+        (None, account) => (None, decode(account)?),
+    };
+    let account = account.trim_start();
+    if account.is_empty() {
+        return Err(invalid("missing account name"));
+    }
+
+    let mut secret = None;
+    let mut issuer = None;
+    let mut algorithm = Algorithm::default();
+    let mut digits = 6;
+    let mut period = 30;
+    let mut counter = None;
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (name, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = decode(&value.replace('+', " "))?;
+        match name {
+            "secret" => secret = Some(base32::decode(&value)?),
+            "issuer" => issuer = Some(value),
+            "algorithm" => algorithm = parse_algorithm(&value)?,
+            "digits" => digits = parse_number(name, &value)?,
+            "period" => period = parse_number(name, &value)?,
+            "counter" => counter = Some(parse_number(name, &value)?),
+            _ => {}
+        }
+    }
+    if let (Some(label_issuer), Some(issuer)) = (&label_issuer, &issuer) {
+        if label_issuer != issuer {
+            return Err(invalid(format!(
+                "label issuer {label_issuer:?} does not match issuer {issuer:?}"
+            )));
+        }
+    }
+    Ok(Parsed {
+        issuer: issuer.or(label_issuer),
+        account: account.to_string(),
+        secret: secret.ok_or_else(|| invalid("missing secret"))?,
+        algorithm,
+        digits,
+        period,
+        counter,
+    })
+}
+
+/// Split a label into its issuer prefix, if any, and account name.
+///
+/// The separator is usually a literal `:`, but may itself be encoded.
+/// A colon within the issuer is always encoded, so the first literal
+/// one is taken as the separator in preference.
+fn split_label(label: &str) -> (Option<&str>, &str) {
+    if let Some((issuer, account)) = label.split_once(':') {
+        return (Some(issuer), account);
+    }
+    let upper = label.to_ascii_uppercase();
+    match upper.find("%3A") {
+        Some(i) => (Some(&label[..i]), &label[i + 3..]),
+        None => (None, label),
+    }
+}
+
+fn parse_algorithm(value: &str) -> OtpResult<Algorithm> {
+    [Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha512]
+        .into_iter()
+        .find(|algorithm| algorithm.uri_name().eq_ignore_ascii_case(value))
+        .ok_or_else(|| invalid(format!("unknown algorithm {value:?}")))
+}
+
+fn parse_number<T: core::str::FromStr>(name: &str, value: &str) -> OtpResult<T> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("invalid {name} {value:?}")))
+}
+
+fn invalid(err: impl Into<String>) -> HotpError {
+    HotpError::InvalidUri { err: err.into() }
+}
+
+/// Percent-encode every byte of `value` other than the RFC 3986
+/// unreserved characters, so that spaces, `@`, `:` and any non-ASCII
+/// characters survive in both the label and the query.
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Decode the percent-encoded bytes of `value`, which must then be
+/// valid UTF-8.
+pub(crate) fn decode(value: &str) -> OtpResult<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let byte = tail
+                .get(..2)
+                .and_then(|hex| core::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| invalid(format!("invalid percent-encoding in {value:?}")))?;
+            bytes.push(byte);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid(format!("{value:?} is not valid UTF-8")))
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use crate::{
+        uri::{decode, encode, parse, Builder},
+        Algorithm, HotpError,
+    };
+
+    #[test_case("ACME", "ACME")]
+    #[test_case("ACME Co", "ACME%20Co")]
+    #[test_case("alice@example.com", "alice%40example.com")]
+    #[test_case("a:b", "a%3Ab")]
+    #[test_case("a+b&c=d", "a%2Bb%26c%3Dd")]
+    #[test_case("Zürich", "Z%C3%BCrich")]
+    fn it_percent_encodes(value: &str, expected: &str) {
+        assert_eq!(encode(value), expected);
+    }
+
+    #[test]
+    fn it_builds_a_uri() {
+        let uri = Builder::new("totp", "ACME Co", "alice@example.com")
+            .param("secret", "GEZDGNBV")
+            .param("issuer", "ACME Co")
+            .build();
+        assert_eq!(
+            uri,
+            "otpauth://totp/ACME%20Co:alice%40example.com?secret=GEZDGNBV&issuer=ACME%20Co"
+        );
+    }
+
+    #[test]
+    fn it_omits_an_empty_issuer() {
+        let uri = Builder::new("totp", "", "alice")
+            .param("issuer", "")
+            .build();
+        assert_eq!(uri, "otpauth://totp/alice");
+    }
+
+    #[test_case("ACME%20Co", "ACME Co")]
+    #[test_case("alice%40example.com", "alice@example.com")]
+    #[test_case("plain", "plain")]
+    fn it_percent_decodes(value: &str, expected: &str) {
+        assert_eq!(decode(value).unwrap(), expected);
+    }
+
+    #[test_case("%" ; "truncated")]
+    #[test_case("%4" ; "one digit")]
+    #[test_case("%GG" ; "not hex")]
+    #[test_case("%FF" ; "not utf-8")]
+    fn it_rejects_invalid_percent_encoding(value: &str) {
+        assert!(matches!(decode(value), Err(HotpError::InvalidUri { .. })));
+    }
+
+    #[test]
+    fn it_parses_a_uri_with_defaults() {
+        let parsed = parse("otpauth://totp/alice?secret=GEZDGNBV", "totp").unwrap();
+        assert_eq!(parsed.issuer, None);
+        assert_eq!(parsed.account, "alice");
+        assert_eq!(parsed.secret, b"12345");
+        assert_eq!(parsed.algorithm, Algorithm::Sha1);
+        assert_eq!(parsed.digits, 6);
+        assert_eq!(parsed.period, 30);
+        assert_eq!(parsed.counter, None);
+    }
+
+    #[test]
+    fn it_parses_all_parameters() {
+        let uri = "otpauth://hotp/ACME:alice?secret=GEZDGNBV&issuer=ACME\
+                   &algorithm=sha512&digits=8&period=60&counter=7&image=x";
+        let parsed = parse(uri, "hotp").unwrap();
+        assert_eq!(parsed.issuer.as_deref(), Some("ACME"));
+        assert_eq!(parsed.account, "alice");
+        assert_eq!(parsed.algorithm, Algorithm::Sha512);
+        assert_eq!(parsed.digits, 8);
+        assert_eq!(parsed.period, 60);
+        assert_eq!(parsed.counter, Some(7));
+    }
+
+    #[test_case("otpauth://totp/ACME: alice?secret=GEZDGNBV", Some("ACME") ; "label only")]
+    #[test_case("otpauth://totp/alice?secret=GEZDGNBV&issuer=ACME", Some("ACME") ; "parameter only")]
+    #[test_case("otpauth://totp/alice?secret=GEZDGNBV", None ; "neither")]
+    fn it_reads_the_issuer_from_the_label_or_parameter(uri: &str, expected: Option<&str>) {
+        let parsed = parse(uri, "totp").unwrap();
+        assert_eq!(parsed.issuer.as_deref(), expected);
+        assert_eq!(parsed.account, "alice");
+    }
+
+    #[test_case("https://totp/alice?secret=GEZDGNBV" ; "wrong scheme")]
+    #[test_case("otpauth://hotp/alice?secret=GEZDGNBV" ; "wrong type")]
+    #[test_case("otpauth://totp" ; "missing label")]
+    #[test_case("otpauth://totp/?secret=GEZDGNBV" ; "missing account")]
+    #[test_case("otpauth://totp/alice" ; "missing secret")]
+    #[test_case("otpauth://totp/alice?secret=GEZDGNBV&algorithm=MD5" ; "unknown algorithm")]
+    #[test_case("otpauth://totp/alice?secret=GEZDGNBV&digits=six" ; "invalid digits")]
+    #[test_case("otpauth://totp/A:alice?secret=GEZDGNBV&issuer=B" ; "mismatched issuer")]
+    fn it_rejects_malformed_uris(uri: &str) {
+        assert!(matches!(
+            parse(uri, "totp"),
+            Err(HotpError::InvalidUri { .. })
+        ));
+    }
+
+    #[test]
+    fn it_round_trips_labels_with_reserved_and_unicode_characters() {
+        let issuer = "Bänk: Ünïcødé & Co. 銀行";
+        let account = "alice+2fa@example.com: personal";
+        let uri = Builder::new("totp", issuer, account)
+            .param("secret", "GEZDGNBV")
+            .param("issuer", issuer)
+            .build();
+        let parsed = parse(&uri, "totp").unwrap();
+        assert_eq!(parsed.issuer.as_deref(), Some(issuer));
+        assert_eq!(parsed.account, account);
+    }
+
+    #[test_case("otpauth://totp/ACME%3Aalice?secret=GEZDGNBV" ; "encoded separator")]
+    #[test_case("otpauth://totp/ACME%3A%20alice?secret=GEZDGNBV" ; "encoded space after the separator")]
+    #[test_case("otpauth://totp/alice?secret=GEZDGNBV&issuer=ACME" ; "issuer parameter")]
+    fn it_accepts_alternative_label_encodings(uri: &str) {
+        let parsed = parse(uri, "totp").unwrap();
+        assert_eq!(parsed.issuer.as_deref(), Some("ACME"));
+        assert_eq!(parsed.account, "alice");
+    }
+
+    #[test]
+    fn it_decodes_plus_as_a_space_in_parameters() {
+        let parsed = parse(
+            "otpauth://totp/alice?secret=GEZDGNBV&issuer=ACME+Co",
+            "totp",
+        )
+        .unwrap();
+        assert_eq!(parsed.issuer.as_deref(), Some("ACME Co"));
+    }
+}
@@ -0,0 +1,88 @@
+//! JavaScript bindings for a browser-based authenticator, built with
+//! `wasm-bindgen`.
+//!
+//! The generator reads the time from JavaScript's `Date.now()`, since
+//! the system clock is unavailable to `wasm32-unknown-unknown`.
+//! Passcodes cross the boundary as zero-padded strings, so that leading
+//! zeroes survive.
+//!
+//! ```js
+//! const totp = new Totp("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", 6, 30);
+//! const code = totp.generate();
+//! totp.verify(code, 1); // true
+//! ```
+
+use unix_time::Instant;
+use wasm_bindgen::prelude::*;
+
+use crate::Totp;
+
+/// A TOTP generator, constructed from a Base32-encoded secret.
+#[wasm_bindgen(js_name = Totp)]
+pub struct WasmTotp {
+    totp: Totp,
+}
+
+#[wasm_bindgen(js_class = Totp)]
+impl WasmTotp {
+    /// Get a generator of SHA-1 passcodes of `digits`, in windows of
+    /// `step` seconds from the Unix epoch.
+    ///
+    /// Throws if the secret is not valid Base32, or as
+    /// [`Totp::new`] does for an invalid number of digits or step.
+    #[wasm_bindgen(constructor)]
+    pub fn new(secret: &str, digits: u32, step: u32) -> Result<WasmTotp, JsError> {
+        let key = crate::base32::decode(secret)?;
+        let now: fn() -> Instant = js_now;
+        let totp = Totp::new_with_now(key, Instant::at(0, 0), step.into(), digits, now)?;
+        Ok(WasmTotp { totp })
+    }
+
+    /// The passcode for the current window, with leading zeroes.
+    pub fn generate(&self) -> Result<String, JsError> {
+        let code = self.totp.get_at(js_now())?;
+        Ok(self.totp.encoding.format(code, self.totp.width()))
+    }
+
+    /// Check a submitted `code`, as typed by the user, against the
+    /// passcodes for the current window and `skew` windows either side.
+    ///
+    /// As for [`Totp::verify_str_with_skew`], leading zeroes must not be
+    /// dropped.
+    pub fn verify(&self, code: &str, skew: u32) -> bool {
+        self.totp.verify_str_with_skew(code, skew.into())
+    }
+
+    /// The number of seconds until the current passcode expires.
+    #[wasm_bindgen(js_name = remainingSeconds)]
+    pub fn remaining_seconds(&self) -> u32 {
+        self.totp.remaining_seconds() as u32
+    }
+}
+
+fn js_now() -> Instant {
+    instant_from_millis(js_sys::Date::now())
+}
+
+/// Convert milliseconds since the Unix epoch, as JavaScript keeps time,
+/// clamping earlier times to the epoch.
+fn instant_from_millis(millis: f64) -> Instant {
+    let millis = millis.max(0.0) as u64;
+    Instant::at(millis / 1000, (millis % 1000) as u32 * 1_000_000)
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+    use unix_time::Instant;
+
+    use crate::wasm::instant_from_millis;
+
+    #[test_case(0.0, Instant::at(0, 0))]
+    #[test_case(59_000.0, Instant::at(59, 0))]
+    #[test_case(1_111_111_109_250.0, Instant::at(1_111_111_109, 250_000_000))]
+    #[test_case(-1_000.0, Instant::at(0, 0) ; "before the epoch")]
+    fn it_converts_javascript_times(millis: f64, expected: Instant) {
+        assert_eq!(instant_from_millis(millis), expected);
+    }
+}